@@ -0,0 +1,255 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{
+    Data, DeriveInput, Fields, GenericParam, Generics, Ident, Lifetime, parse_macro_input,
+    parse_quote,
+};
+
+// `#[derive(Serializable)]` for mini-desert-json.
+//
+// Structs with named fields are read from JSON objects, every field has to be
+// present exactly once. Fields the struct does not have are skipped, or
+// rejected with `#[desert(deny_unknown_fields)]` on the struct. C-like enums
+// are read from the name of the variant as a string.
+#[proc_macro_derive(Serializable, attributes(desert))]
+pub fn derive_serializable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_serializable(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// `#[derive(Encodable)]`, the other direction in the same format: structs
+// are written as objects with their fields in declaration order and C-like
+// enums as the name of the variant.
+#[proc_macro_derive(Encodable, attributes(desert))]
+pub fn derive_encodable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encodable(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// What the derives support: structs with named fields and enums whose
+// variants have no fields.
+enum Shape<'a> {
+    Struct(Vec<&'a Ident>),
+    Enum(Vec<&'a Ident>),
+}
+
+fn shape<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<Shape<'a>> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(Shape::Struct(
+                fields.named.iter().flat_map(|f| &f.ident).collect(),
+            )),
+            _ => Err(syn::Error::new_spanned(
+                &input.ident,
+                format!("{derive} can only be derived for structs with named fields"),
+            )),
+        },
+        Data::Enum(data) => {
+            if let Some(variant) = data.variants.iter().find(|v| !v.fields.is_empty()) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    format!("{derive} can only be derived for enums without fields"),
+                ));
+            }
+            Ok(Shape::Enum(
+                data.variants.iter().map(|v| &v.ident).collect(),
+            ))
+        }
+        Data::Union(data) => Err(syn::Error::new_spanned(
+            data.union_token,
+            format!("{derive} cannot be derived for unions"),
+        )),
+    }
+}
+
+fn expand_serializable(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let deny_unknown_fields = deny_unknown_fields(&input)?;
+    let de = input_lifetime(&input.generics);
+    let body = match shape(&input, "Serializable")? {
+        Shape::Struct(names) => expand_struct(&input.ident, &names, &de, deny_unknown_fields),
+        Shape::Enum(variants) => expand_enum(&input.ident, &variants, &de),
+    };
+
+    // The input outlives everything borrowed from it, and every type parameter
+    // has to be deserializable itself.
+    let ident = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let lifetimes: Vec<_> = input
+        .generics
+        .lifetimes()
+        .map(|l| l.lifetime.clone())
+        .collect();
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::mini_desert_json::Serializable<#de>));
+    }
+    let de_lifetime: GenericParam = if lifetimes.is_empty() {
+        parse_quote!(#de)
+    } else {
+        parse_quote!(#de: #(#lifetimes)+*)
+    };
+    generics.params.insert(0, de_lifetime);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::mini_desert_json::Serializable<#de> for #ident #ty_generics
+        #where_clause
+        {
+            fn deserialize(
+                deserializer: &mut ::mini_desert_json::Deserializer<#de>,
+            ) -> ::mini_desert_json::Result<Self> {
+                #body
+            }
+        }
+    })
+}
+
+// The lifetime of the input, `'se` unless the type has a lifetime of that name
+// already.
+fn input_lifetime(generics: &Generics) -> Lifetime {
+    let taken: Vec<_> = generics
+        .lifetimes()
+        .map(|l| l.lifetime.ident.to_string())
+        .collect();
+    let mut name = "se".to_string();
+    while taken.contains(&name) {
+        name.push('_');
+    }
+    Lifetime::new(&format!("'{name}"), Span::call_site())
+}
+
+// Whether the container has `#[desert(deny_unknown_fields)]`.
+fn deny_unknown_fields(input: &DeriveInput) -> syn::Result<bool> {
+    let mut deny = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("desert")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("deny_unknown_fields") {
+                deny = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown desert attribute"))
+            }
+        })?;
+    }
+    Ok(deny)
+}
+
+// Each field is collected into an `Option` while the object is read, in
+// whatever order the entries come, and unwrapped once it is done.
+fn expand_struct(
+    ident: &Ident,
+    names: &[&Ident],
+    de: &Lifetime,
+    deny_unknown_fields: bool,
+) -> TokenStream2 {
+    // The variables are numbered rather than named after the fields, which
+    // could clash with the names used here.
+    let vars: Vec<_> = (0..names.len())
+        .map(|i| format_ident!("__field{i}"))
+        .collect();
+    let keys: Vec<_> = names.iter().map(|name| name.unraw().to_string()).collect();
+    let unknown = if deny_unknown_fields {
+        quote! {
+            return Err(::mini_desert_json::ErrorCode::UnknownField(key.to_string()).into());
+        }
+    } else {
+        quote! {
+            <::mini_desert_json::IgnoredAny as ::mini_desert_json::Serializable>::deserialize(
+                deserializer,
+            )?;
+        }
+    };
+    quote! {
+        #(let mut #vars = None;)*
+        deserializer.parse_map(|deserializer, key: ::std::borrow::Cow<#de, str>| {
+            match &*key {
+                #(
+                    #keys => {
+                        if #vars.is_some() {
+                            return Err(::mini_desert_json::ErrorCode::DuplicateField(#keys).into());
+                        }
+                        #vars = Some(::mini_desert_json::Serializable::deserialize(deserializer)?);
+                    }
+                )*
+                _ => { #unknown }
+            }
+            Ok(())
+        })?;
+        Ok(#ident {
+            #(
+                #names: #vars.ok_or(::mini_desert_json::ErrorCode::MissingField(#keys))?,
+            )*
+        })
+    }
+}
+
+fn expand_enum(ident: &Ident, variants: &[&Ident], de: &Lifetime) -> TokenStream2 {
+    let names: Vec<_> = variants
+        .iter()
+        .map(|variant| variant.unraw().to_string())
+        .collect();
+    quote! {
+        let name =
+            <::std::borrow::Cow<#de, str> as ::mini_desert_json::Serializable>::deserialize(
+                deserializer,
+            )?;
+        match &*name {
+            #(#names => Ok(#ident::#variants),)*
+            _ => Err(::mini_desert_json::ErrorCode::UnknownVariant(name.into_owned()).into()),
+        }
+    }
+}
+
+fn expand_encodable(input: DeriveInput) -> syn::Result<TokenStream2> {
+    // Only deserializing has options so far, but the attribute is shared and
+    // should be checked either way.
+    deny_unknown_fields(&input)?;
+    let ident = &input.ident;
+    let body = match shape(&input, "Encodable")? {
+        Shape::Struct(names) => {
+            let keys = names.iter().map(|name| name.unraw().to_string());
+            quote! {
+                let mut object = serializer.write_object()?;
+                #(object.entry(#keys, &self.#names)?;)*
+                object.end()
+            }
+        }
+        Shape::Enum(variants) => {
+            let names = variants.iter().map(|variant| variant.unraw().to_string());
+            quote! {
+                serializer.write_str(match self {
+                    #(#ident::#variants => #names,)*
+                })
+            }
+        }
+    };
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::mini_desert_json::Encodable));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // The writer's type parameter is underscored so it cannot shadow one of
+    // the type's own.
+    Ok(quote! {
+        impl #impl_generics ::mini_desert_json::Encodable for #ident #ty_generics #where_clause {
+            fn encode<__W: ::std::io::Write>(
+                &self,
+                serializer: &mut ::mini_desert_json::Serializer<__W>,
+            ) -> ::mini_desert_json::Result<()> {
+                #body
+            }
+        }
+    })
+}
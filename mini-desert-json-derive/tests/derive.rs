@@ -0,0 +1,153 @@
+use mini_desert_json::{Encodable, ErrorCode, Serializable, from_str, to_string};
+use std::collections::HashMap;
+
+#[derive(Serializable, Encodable, Debug, PartialEq)]
+struct Server<'a> {
+    host: &'a str,
+    port: u32,
+    tags: Vec<String>,
+    r#type: Kind,
+}
+
+#[derive(Serializable, Encodable, Debug, PartialEq)]
+enum Kind {
+    Primary,
+    Replica,
+}
+
+#[derive(Serializable, Debug, PartialEq)]
+#[desert(deny_unknown_fields)]
+struct Strict {
+    key: bool,
+}
+
+#[derive(Serializable, Encodable, Debug, PartialEq)]
+struct Wrapper<T> {
+    deserializer: T,
+}
+
+// Has a lifetime of the same name as the one the derive uses for the input.
+#[derive(Serializable, Encodable, Debug, PartialEq)]
+struct Named<'se> {
+    name: &'se str,
+}
+
+// Has a type parameter of the same name as the writer of `Encodable::encode`.
+#[derive(Serializable, Encodable, Debug, PartialEq)]
+struct Labeled<W> {
+    label: W,
+}
+
+#[test]
+fn test_derive_struct() {
+    let json = r#"{
+        "port": 5432,
+        "comment": {"ignored": [1, "two", null, {"x": -1.5e3}, true]},
+        "host": "db1",
+        "type": "Replica",
+        "tags": ["eu", "ssd"]
+    }"#;
+    assert_eq!(
+        from_str::<Server>(json).unwrap(),
+        Server {
+            host: "db1",
+            port: 5432,
+            tags: vec!["eu".to_string(), "ssd".to_string()],
+            r#type: Kind::Replica,
+        }
+    );
+    assert!(matches!(
+        from_str::<Server>(r#"{"host":"db1","port":1,"tags":[]}"#)
+            .unwrap_err()
+            .code(),
+        ErrorCode::MissingField("type")
+    ));
+    assert!(matches!(
+        from_str::<Server>(r#"{"port":1,"port":2}"#)
+            .unwrap_err()
+            .code(),
+        ErrorCode::DuplicateField("port")
+    ));
+    assert!(matches!(
+        from_str::<Server>("[]").unwrap_err().code(),
+        ErrorCode::ExpectedMap
+    ));
+
+    assert_eq!(
+        from_str::<Vec<Wrapper<u32>>>(r#"[{"deserializer":1}]"#).unwrap(),
+        vec![Wrapper { deserializer: 1 }]
+    );
+    assert_eq!(
+        from_str::<HashMap<&str, Strict>>(r#"{"a":{"key":true}}"#).unwrap(),
+        HashMap::from([("a", Strict { key: true })])
+    );
+    assert!(
+        matches!(from_str::<Strict>(r#"{"key":true,"other":1}"#).unwrap_err().code(), ErrorCode::UnknownField(field) if field == "other")
+    );
+    // Keys are matched after their escapes are decoded.
+    assert_eq!(
+        from_str::<Wrapper<Vec<String>>>(r#"{"deserializ\u0065r":["a\"b"]}"#).unwrap(),
+        Wrapper {
+            deserializer: vec!["a\"b".to_string()]
+        }
+    );
+    assert_eq!(
+        from_str::<Named>(r#"{"name":"x"}"#).unwrap(),
+        Named { name: "x" }
+    );
+}
+
+#[test]
+fn test_derive_enum() {
+    assert_eq!(from_str::<Kind>(r#""Primary""#).unwrap(), Kind::Primary);
+    assert_eq!(
+        from_str::<Kind>(r#""\u0050rimary""#).unwrap(),
+        Kind::Primary
+    );
+    assert_eq!(
+        from_str::<Vec<Kind>>(r#"["Replica","Primary"]"#).unwrap(),
+        vec![Kind::Replica, Kind::Primary]
+    );
+    assert!(
+        matches!(from_str::<Kind>(r#""primary""#).unwrap_err().code(), ErrorCode::UnknownVariant(variant) if variant == "primary")
+    );
+    assert!(matches!(
+        from_str::<Kind>("1").unwrap_err().code(),
+        ErrorCode::ExpectedString
+    ));
+}
+
+#[test]
+fn test_derive_encodable() {
+    let servers = vec![
+        Server {
+            host: "db1",
+            port: 5432,
+            tags: vec!["eu".to_string()],
+            r#type: Kind::Primary,
+        },
+        Server {
+            host: "db2",
+            port: 5433,
+            tags: vec![],
+            r#type: Kind::Replica,
+        },
+    ];
+    let json = to_string(&servers).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"host":"db1","port":5432,"tags":["eu"],"type":"Primary"},{"host":"db2","port":5433,"tags":[],"type":"Replica"}]"#
+    );
+    assert_eq!(from_str::<Vec<Server>>(&json).unwrap(), servers);
+    assert_eq!(
+        to_string(&Wrapper { deserializer: -1.5 }).unwrap(),
+        r#"{"deserializer":-1.5}"#
+    );
+
+    let named = Named { name: "x" };
+    assert_eq!(to_string(&named).unwrap(), r#"{"name":"x"}"#);
+    let labeled = Labeled { label: named };
+    let json = to_string(&labeled).unwrap();
+    assert_eq!(json, r#"{"label":{"name":"x"}}"#);
+    assert_eq!(from_str::<Labeled<Named>>(&json).unwrap(), labeled);
+}
@@ -1,11 +1,81 @@
-use crate::error::{Error, Result};
-use std::collections::HashMap;
+use crate::error::{Error, ErrorCode, Result};
+use crate::token::{Token, Tokenizer, unescape};
+use std::any::type_name;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::hash::Hash;
-use std::ops::{AddAssign, MulAssign};
+use std::str::FromStr;
 
+// Deserialize the JSON document in `input`. Nothing but whitespace may follow
+// the value, and duplicate keys in maps are errors. For other options build a
+// `Deserializer`.
 pub fn from_str<'se, T: Serializable<'se>>(input: &'se str) -> Result<T> {
-    let mut deserializer = Deserializer::new(input);
+    from_str_with_limits(input, Limits::default())
+}
+
+// Like `from_str` but with custom limits, for input from untrusted sources.
+pub fn from_str_with_limits<'se, T: Serializable<'se>>(
+    input: &'se str,
+    limits: Limits,
+) -> Result<T> {
+    let mut deserializer = Deserializer::with_limits(input, limits);
     T::deserialize(&mut deserializer)
+        .and_then(|value| deserializer.end().map(|()| value))
+        .map_err(|err| deserializer.locate(err))
+}
+
+// Deserialize only the value `pointer` refers to, a JSON Pointer (RFC 6901)
+// like `/users/3/email`: each segment is the key of an object or the index of
+// an array, with `~1` standing for `/` and `~0` for `~` in keys. The empty
+// pointer is the whole document.
+//
+// Everything before the value is skipped over token by token, without being
+// deserialized, and nothing after it is looked at. Keys are compared once
+// their escapes are decoded, so `/a~1b` finds `"a\/b"` as well as `"a/b"`.
+pub fn from_str_at<'se, T: Serializable<'se>>(input: &'se str, pointer: &str) -> Result<T> {
+    let mut deserializer = Deserializer::new(input);
+    deserializer
+        .seek(pointer)
+        .and_then(|()| T::deserialize(&mut deserializer))
+        .map_err(|err| deserializer.locate(err))
+}
+
+// Bounds on what the deserializer is willing to parse. Maps and arrays are
+// parsed by recursion, so nesting is limited by default to keep deeply nested
+// input from overflowing the stack. Strings and collections are unlimited
+// unless asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    // How many maps and arrays may be nested inside each other.
+    pub max_depth: usize,
+    // The longest string in bytes.
+    pub max_string_len: usize,
+    // The most elements in one array or entries in one map.
+    pub max_collection_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 128,
+            max_string_len: usize::MAX,
+            max_collection_len: usize::MAX,
+        }
+    }
+}
+
+// What maps do with a key they already have. Structs derived with
+// `#[derive(Serializable)]` always reject duplicate fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    // Fail with `ErrorCode::DuplicateKey`, as a repeated key is more likely
+    // a corrupt or hand-merged document than intended.
+    #[default]
+    Error,
+    // Keep the first value, the later ones are skipped unread.
+    FirstWins,
+    // Keep the last value, as most JSON parsers do.
+    LastWins,
 }
 
 pub trait Serializable<'se>: Sized {
@@ -15,11 +85,22 @@ pub trait Serializable<'se>: Sized {
 // Impl for String
 impl<'se> Serializable<'se> for String {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self> {
-        deserializer.parse_string().map(|s| s.to_string())
+        deserializer.parse_string().map(Cow::into_owned)
     }
 }
 
+// Borrows from the input, so only strings without escapes can be read this
+// way. `Cow<str>` borrows when it can and takes a copy when it has to.
 impl<'se> Serializable<'se> for &'se str {
+    fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+        match deserializer.parse_string()? {
+            Cow::Borrowed(s) => Ok(s),
+            Cow::Owned(_) => Err(ErrorCode::EscapedStr.into()),
+        }
+    }
+}
+
+impl<'se> Serializable<'se> for Cow<'se, str> {
     fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
         deserializer.parse_string()
     }
@@ -32,17 +113,63 @@ impl<'se> Serializable<'se> for bool {
     }
 }
 
-// Impl for u32
-impl<'se> Serializable<'se> for u32 {
+// Impls for the integer types. The number has to fit, `300` is out of range
+// for a `u8` rather than wrapping around.
+macro_rules! impl_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl<'se> Serializable<'se> for $ty {
+                fn deserialize(deserializer: &mut Deserializer) -> Result<Self> {
+                    deserializer.parse_integer()
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+// Impl for f32
+impl<'se> Serializable<'se> for f32 {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self> {
-        deserializer.parse_unsigned()
+        deserializer.parse_float()
     }
 }
 
-// Impl for u64
-impl<'se> Serializable<'se> for u64 {
+// Impl for f64
+impl<'se> Serializable<'se> for f64 {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self> {
-        deserializer.parse_unsigned()
+        deserializer.parse_float()
+    }
+}
+
+// A `char` is a string holding exactly one character.
+impl<'se> Serializable<'se> for char {
+    fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+        let s = deserializer.parse_string()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Ok(ch),
+            _ => Err(ErrorCode::ExpectedChar.into()),
+        }
+    }
+}
+
+// `null` is `None`, anything else is `Some`.
+impl<'se, T: Serializable<'se>> Serializable<'se> for Option<T> {
+    fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+        if deserializer.peek_token()? == Token::Null {
+            deserializer.parse_null()?;
+            Ok(None)
+        } else {
+            T::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+impl<'se, T: Serializable<'se>> Serializable<'se> for Box<T> {
+    fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+        T::deserialize(deserializer).map(Box::new)
     }
 }
 
@@ -53,165 +180,413 @@ where
 {
     fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
         let mut map = HashMap::new();
-
-        // Parse the opening brace of the map.
-        if deserializer.next_char()? != '{' {
-            return Err(Error::ExpectedMap);
-        }
-        let mut first = true;
-        loop {
-            // Check for closing brace
-            if deserializer.peek_char()? == '}' {
-                deserializer.next_char()?; // Consume '}'
-                break;
+        deserializer.parse_map(|deserializer, key: K| {
+            if let Some(value) = map_value(deserializer, map.contains_key(&key))? {
+                map.insert(key, value);
             }
-
-            // Comma handling
-            if !first {
-                if deserializer.next_char()? != ',' {
-                    return Err(Error::ExpectedMapComma);
-                }
-            } else {
-                first = false;
-            }
-
-            // Deserialize key
-            let key = K::deserialize(deserializer)?; // Deserialize K
-
-            // Colon
-            if deserializer.next_char()? != ':' {
-                return Err(Error::ExpectedMapColon);
-            }
-
-            // Deserialize value
-            let value = V::deserialize(deserializer)?; // Deserialize V
-
-            map.insert(key, value);
-        }
+            Ok(())
+        })?;
         Ok(map)
     }
 }
 
-impl<'se, V> Serializable<'se> for Vec<V>
+impl<'se, K, V> Serializable<'se> for BTreeMap<K, V>
 where
+    K: Serializable<'se> + Ord,
     V: Serializable<'se>,
 {
     fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
-        let mut vec = Vec::new();
+        let mut map = BTreeMap::new();
+        deserializer.parse_map(|deserializer, key: K| {
+            if let Some(value) = map_value(deserializer, map.contains_key(&key))? {
+                map.insert(key, value);
+            }
+            Ok(())
+        })?;
+        Ok(map)
+    }
+}
 
-        // Parse the opening bracket of the array.
-        if deserializer.next_char()? != '[' {
-            return Err(Error::ExpectedArray);
-        }
+// Read the value of a map entry whose key the map may already have, going by
+// the deserializer's `DuplicateKeys`. `None` if the value is not to be kept.
+fn map_value<'se, V: Serializable<'se>>(
+    deserializer: &mut Deserializer<'se>,
+    duplicate: bool,
+) -> Result<Option<V>> {
+    if !duplicate {
+        return V::deserialize(deserializer).map(Some);
+    }
+    match deserializer.duplicate_keys {
+        DuplicateKeys::Error => Err(ErrorCode::DuplicateKey.into()),
+        DuplicateKeys::FirstWins => IgnoredAny::deserialize(deserializer).map(|_| None),
+        DuplicateKeys::LastWins => V::deserialize(deserializer).map(Some),
+    }
+}
 
-        let mut first = true;
-        loop {
-            // Check for closing bracket
-            if deserializer.peek_char()? == ']' {
-                deserializer.next_char()?; // Consume ']'
-                break;
+// Impls for the collections read from arrays, one element after another.
+macro_rules! impl_sequence {
+    ($($ty:ident<T $(: $bound:ident $(+ $more:ident)*)?> => $insert:ident,)*) => {
+        $(
+            impl<'se, T> Serializable<'se> for $ty<T>
+            where
+                T: Serializable<'se> $(+ $bound $(+ $more)*)?,
+            {
+                fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+                    let mut collection = $ty::new();
+                    deserializer.parse_seq(|deserializer| {
+                        collection.$insert(T::deserialize(deserializer)?);
+                        Ok(())
+                    })?;
+                    Ok(collection)
+                }
             }
+        )*
+    };
+}
 
-            // Comma handling
-            if !first {
-                if deserializer.next_char()? != ',' {
-                    return Err(Error::ExpectedArrayComma);
+impl_sequence! {
+    Vec<T> => push,
+    VecDeque<T> => push_back,
+    HashSet<T: Eq + Hash> => insert,
+    BTreeSet<T: Ord> => insert,
+}
+
+// A fixed-size array has to have exactly `N` elements.
+impl<'se, T: Serializable<'se>, const N: usize> Serializable<'se> for [T; N] {
+    fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+        let vec = Vec::<T>::deserialize(deserializer)?;
+        vec.try_into()
+            .map_err(|_| ErrorCode::InvalidLength(N).into())
+    }
+}
+
+// Tuples are arrays with one element per field, `(1, "a")` is `[1,"a"]`.
+macro_rules! impl_tuple {
+    ($len:literal => $($idx:tt $ty:ident $var:ident)+) => {
+        impl<'se, $($ty: Serializable<'se>),+> Serializable<'se> for ($($ty,)+) {
+            fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+                $(let mut $var = None;)+
+                deserializer.parse_seq_indexed(|deserializer, index| {
+                    match index {
+                        $($idx => $var = Some($ty::deserialize(deserializer)?),)+
+                        _ => return Err(ErrorCode::InvalidLength($len).into()),
+                    }
+                    Ok(())
+                })?;
+                match ($($var,)+) {
+                    ($(Some($var),)+) => Ok(($($var,)+)),
+                    _ => Err(ErrorCode::InvalidLength($len).into()),
                 }
-            } else {
-                first = false;
             }
-
-            // Deserialize element
-            let element = V::deserialize(deserializer)?;
-            vec.push(element);
         }
-        Ok(vec)
+    };
+}
+
+impl_tuple!(1 => 0 T0 t0);
+impl_tuple!(2 => 0 T0 t0 1 T1 t1);
+impl_tuple!(3 => 0 T0 t0 1 T1 t1 2 T2 t2);
+impl_tuple!(4 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3);
+impl_tuple!(5 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4);
+impl_tuple!(6 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5);
+impl_tuple!(7 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5 6 T6 t6);
+impl_tuple!(8 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5 6 T6 t6 7 T7 t7);
+impl_tuple!(9 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5 6 T6 t6 7 T7 t7 8 T8 t8);
+impl_tuple!(10 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5 6 T6 t6 7 T7 t7 8 T8 t8 9 T9 t9);
+impl_tuple!(11 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5 6 T6 t6 7 T7 t7 8 T8 t8 9 T9 t9
+    10 T10 t10);
+impl_tuple!(12 => 0 T0 t0 1 T1 t1 2 T2 t2 3 T3 t3 4 T4 t4 5 T5 t5 6 T6 t6 7 T7 t7 8 T8 t8 9 T9 t9
+    10 T10 t10 11 T11 t11);
+
+// Reads any JSON value and throws it away, for skipping the parts of the
+// input that are of no interest, like unknown fields of a struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IgnoredAny;
+
+impl<'se> Serializable<'se> for IgnoredAny {
+    fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+        deserializer.skip_value()?;
+        Ok(IgnoredAny)
     }
 }
 
+// Reads values out of the tokens of a `Tokenizer`. The tokenizer takes care
+// of the punctuation and the limits, the deserializer only checks that each
+// token is what the type being read expects.
+//
+// `from_str` covers the common case. Building a deserializer allows for
+// other options:
+//
+//     let mut deserializer = Deserializer::new(input).duplicate_keys(DuplicateKeys::LastWins);
+//     let value = Config::deserialize(&mut deserializer)?;
+//     deserializer.end()?;
 pub struct Deserializer<'de> {
-    input: &'de str,
+    tokenizer: Tokenizer<'de>,
+    // A token that has been looked at but not consumed.
+    peeked: Option<Token<'de>>,
+    duplicate_keys: DuplicateKeys,
 }
 
 impl<'de> Deserializer<'de> {
-    fn new(input: &'de str) -> Self {
-        Self { input }
+    pub fn new(input: &'de str) -> Self {
+        Deserializer::with_limits(input, Limits::default())
     }
 
-    fn peek_char(&self) -> Result<char> {
-        self.input.chars().next().ok_or(Error::Eof)
+    pub fn with_limits(input: &'de str, limits: Limits) -> Self {
+        Self {
+            tokenizer: Tokenizer::with_limits(input, limits),
+            peeked: None,
+            duplicate_keys: DuplicateKeys::default(),
+        }
     }
 
-    fn next_char(&mut self) -> Result<char> {
-        let c = self.peek_char()?;
-        self.input = &self.input[c.len_utf8()..];
-        Ok(c)
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
     }
 
-    // Parse the JSON identifier `true` or `false`.
-    fn parse_bool(&mut self) -> Result<bool> {
-        if self.input.starts_with("true") {
-            self.input = &self.input["true".len()..];
-            Ok(true)
-        } else if self.input.starts_with("false") {
-            self.input = &self.input["false".len()..];
-            Ok(false)
-        } else {
-            Err(Error::ExpectedBoolean)
+    // Check that the whole input has been read, with nothing but whitespace
+    // after the value. Without this, anything may follow it.
+    pub fn end(&mut self) -> Result<()> {
+        if self.peeked.is_some() {
+            return Err(ErrorCode::TrailingCharacters.into());
+        }
+        self.tokenizer.end()
+    }
+
+    // The next token, without consuming it. Running out of tokens is an
+    // error here, as whoever asks is still in the middle of reading a value.
+    pub fn peek_token(&mut self) -> Result<Token<'de>> {
+        if let Some(token) = self.peeked {
+            return Ok(token);
+        }
+        let token = self.tokenizer.next_token()?.ok_or(ErrorCode::Eof)?;
+        self.peeked = Some(token);
+        Ok(token)
+    }
+
+    pub fn next_token(&mut self) -> Result<Token<'de>> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => Ok(self.tokenizer.next_token()?.ok_or(ErrorCode::Eof)?),
         }
     }
 
-    // Parse a group of decimal digits as an unsigned integer of type T.
-    //
-    // This implementation is a bit too lenient, for example `001` is not
-    // allowed in JSON. Also the various arithmetic operations can overflow and
-    // panic or return bogus data. But it is good enough for example code!
-    fn parse_unsigned<T>(&mut self) -> Result<T>
-    where
-        T: AddAssign<T> + MulAssign<T> + From<u8>,
-    {
-        let mut int = match self.next_char()? {
-            ch @ '0'..='9' => T::from(ch as u8 - b'0'),
-            _ => {
-                return Err(Error::ExpectedInteger);
+    // The next token, which should be the start of a value. If the input has
+    // something that starts no value at all, the error is `err`, which says
+    // what kind of value was expected.
+    fn next_value(&mut self, code: ErrorCode) -> Result<Token<'de>> {
+        self.next_token().map_err(|mut err| {
+            if matches!(err.code(), ErrorCode::ExpectedValue) {
+                err.set_code(code);
             }
-        };
-        loop {
-            match self.input.chars().next() {
-                Some(ch @ '0'..='9') => {
-                    self.input = &self.input[1..];
-                    int *= T::from(10);
-                    int += T::from(ch as u8 - b'0');
+            err
+        })
+    }
+
+    // Errors from the tokenizer already have a position, the ones raised by
+    // `Serializable` impls get the position of the last token.
+    fn locate(&self, err: Error) -> Error {
+        self.tokenizer.locate_token(err)
+    }
+
+    // Skip the next value, with everything nested in it.
+    fn skip_value(&mut self) -> Result<()> {
+        match self.next_token()? {
+            Token::StartArray | Token::StartObject => {
+                // The tokenizer is inside the container now, whether the token
+                // was peeked or not. It is done once it has left it again.
+                let depth = self.tokenizer.depth();
+                while self.tokenizer.depth() >= depth {
+                    self.tokenizer.next_token()?.ok_or(ErrorCode::Eof)?;
                 }
-                _ => {
-                    return Ok(int);
+                Ok(())
+            }
+            Token::String(_) | Token::Number(_) | Token::Bool(_) | Token::Null => Ok(()),
+            Token::Key(_) | Token::EndArray | Token::EndObject => {
+                Err(ErrorCode::ExpectedValue.into())
+            }
+        }
+    }
+
+    // Move to the value `pointer` refers to, see `from_str_at`.
+    fn seek(&mut self, pointer: &str) -> Result<()> {
+        if pointer.is_empty() {
+            return Ok(());
+        }
+        let Some(segments) = pointer.strip_prefix('/') else {
+            return Err(ErrorCode::InvalidPointer(pointer.to_string()).into());
+        };
+        // The end of the current segment in `pointer`.
+        let mut end = 0;
+        for raw in segments.split('/') {
+            end += 1 + raw.len();
+            // Names the pointer up to the segment that could not be found.
+            let not_found = || Error::from(ErrorCode::PointerNotFound(pointer[..end].to_string()));
+            let segment = unescape_segment(raw)
+                .ok_or_else(|| ErrorCode::InvalidPointer(pointer.to_string()))?;
+            match self.next_token()? {
+                Token::StartObject => loop {
+                    match self.next_token()? {
+                        Token::Key(key) if unescape(key) == segment => break,
+                        Token::Key(_) => self.skip_value()?,
+                        _ => return Err(not_found()),
+                    }
+                },
+                Token::StartArray => {
+                    let index = array_index(&segment).ok_or_else(not_found)?;
+                    for i in 0..=index {
+                        if self.peek_token()? == Token::EndArray {
+                            return Err(not_found());
+                        }
+                        if i < index {
+                            self.skip_value()?;
+                        }
+                    }
                 }
+                _ => return Err(not_found()),
+            }
+        }
+        Ok(())
+    }
+
+    // Parse a JSON object, calling `entry` with each key. `entry` has to
+    // parse the value that follows, the deserializer is positioned at it.
+    // This is how maps and derived impls for structs read their entries.
+    pub fn parse_map<K: Serializable<'de>>(
+        &mut self,
+        mut entry: impl FnMut(&mut Self, K) -> Result<()>,
+    ) -> Result<()> {
+        if self.next_value(ErrorCode::ExpectedMap)? != Token::StartObject {
+            return Err(ErrorCode::ExpectedMap.into());
+        }
+        loop {
+            if self.peek_token()? == Token::EndObject {
+                self.next_token()?;
+                return Ok(());
             }
+            // The key arrives as a `Token::Key`, which everything that can be
+            // read from a string accepts.
+            let key = K::deserialize(self)?;
+            entry(self, key)?;
         }
     }
 
-    // Parses a string until the next '"' character.
-    //
-    // Makes no attempt to handle escape sequences. What did you expect? This is
-    // example code!
-    fn parse_string(&mut self) -> Result<&'de str> {
-        if self.next_char()? != '"' {
-            return Err(Error::ExpectedString);
+    // Parse a JSON array, calling `element` for each element with the
+    // deserializer positioned at it. `element` has to parse it.
+    pub fn parse_seq(&mut self, mut element: impl FnMut(&mut Self) -> Result<()>) -> Result<()> {
+        self.parse_seq_indexed(|deserializer, _| element(deserializer))
+    }
+
+    // Like `parse_seq`, but also passing the index of each element.
+    fn parse_seq_indexed(
+        &mut self,
+        mut element: impl FnMut(&mut Self, usize) -> Result<()>,
+    ) -> Result<()> {
+        if self.next_value(ErrorCode::ExpectedArray)? != Token::StartArray {
+            return Err(ErrorCode::ExpectedArray.into());
         }
-        match self.input.find('"') {
-            Some(len) => {
-                let s = &self.input[..len];
-                self.input = &self.input[len + 1..];
-                Ok(s)
+        let mut len = 0;
+        loop {
+            if self.peek_token()? == Token::EndArray {
+                self.next_token()?;
+                return Ok(());
             }
-            None => Err(Error::Eof),
+            element(self, len)?;
+            len += 1;
+        }
+    }
+
+    // Parse the JSON identifier `null`.
+    fn parse_null(&mut self) -> Result<()> {
+        match self.next_value(ErrorCode::ExpectedValue)? {
+            Token::Null => Ok(()),
+            _ => Err(ErrorCode::ExpectedValue.into()),
+        }
+    }
+
+    // Parse the JSON identifier `true` or `false`.
+    fn parse_bool(&mut self) -> Result<bool> {
+        match self.next_value(ErrorCode::ExpectedBoolean)? {
+            Token::Bool(b) => Ok(b),
+            _ => Err(ErrorCode::ExpectedBoolean.into()),
+        }
+    }
+
+    // Parse a JSON integer as an integer of type T. The standard library does
+    // the conversion and checks for overflow, so a number that does not fit
+    // in T, including a negative number for an unsigned T, is an error
+    // instead of wrapping around. Numbers with a fraction or an exponent are
+    // not integers, even `1.0`.
+    fn parse_integer<T: FromStr>(&mut self) -> Result<T> {
+        match self.next_value(ErrorCode::ExpectedInteger)? {
+            Token::Number(number) if !number.contains(['.', 'e', 'E']) => number
+                .parse()
+                .map_err(|_| ErrorCode::NumberOutOfRange(type_name::<T>()).into()),
+            _ => Err(ErrorCode::ExpectedInteger.into()),
+        }
+    }
+
+    // Parse a JSON number as a float of type T. Numbers too large for T,
+    // which the standard library turns into infinity, are rejected.
+    fn parse_float<T: FromStr + Into<f64> + Copy>(&mut self) -> Result<T> {
+        let Token::Number(number) = self.next_value(ErrorCode::ExpectedFloat)? else {
+            return Err(ErrorCode::ExpectedFloat.into());
+        };
+        let float: T = number.parse().map_err(|_| ErrorCode::ExpectedFloat)?;
+        if float.into().is_infinite() {
+            return Err(ErrorCode::NumberOutOfRange(type_name::<T>()).into());
+        }
+        Ok(float)
+    }
+
+    // Parse a string, which can be an object key as well. Borrowed from the
+    // input unless it has escapes to decode.
+    fn parse_string(&mut self) -> Result<Cow<'de, str>> {
+        match self.next_value(ErrorCode::ExpectedString)? {
+            Token::String(s) | Token::Key(s) => Ok(unescape(s)),
+            _ => Err(ErrorCode::ExpectedString.into()),
         }
     }
 }
 
+// Undo the escapes of a JSON Pointer segment. `None` for a `~` that is not
+// followed by `0` or `1`.
+fn unescape_segment(segment: &str) -> Option<Cow<'_, str>> {
+    if !segment.contains('~') {
+        return Some(Cow::Borrowed(segment));
+    }
+    let mut unescaped = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(ch) = chars.next() {
+        unescaped.push(match ch {
+            '~' => match chars.next()? {
+                '0' => '~',
+                '1' => '/',
+                _ => return None,
+            },
+            ch => ch,
+        });
+    }
+    Some(Cow::Owned(unescaped))
+}
+
+// A segment refers to an array element if it is a number without leading
+// zeros. `-`, the element after the last, never exists in a document.
+fn array_index(segment: &str) -> Option<usize> {
+    let digits = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+    segment.parse().ok()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::desert::from_str;
+    use crate::Result;
+    use crate::desert::{
+        Deserializer, IgnoredAny, Limits, Serializable, from_str, from_str_at, from_str_with_limits,
+    };
+    use crate::error::ErrorCode;
     use std::collections::HashMap;
 
     #[test]
@@ -278,4 +653,397 @@ mod tests {
 
         assert!(from_str::<Vec<u32>>(expected).unwrap().is_empty());
     }
+
+    #[test]
+    fn test_deserialize_numbers() {
+        assert_eq!(from_str::<u64>("18446744073709551615").unwrap(), u64::MAX);
+        assert_eq!(from_str::<i32>("-2147483648").unwrap(), i32::MIN);
+        assert_eq!(from_str::<i64>("-0").unwrap(), 0);
+        assert_eq!(from_str::<f64>("-12.5e-1").unwrap(), -1.25);
+        assert_eq!(from_str::<f64>("1E+2").unwrap(), 100.0);
+        assert_eq!(from_str::<f32>("0.1").unwrap(), 0.1);
+        assert_eq!(from_str::<Vec<i64>>("[1,-2,0]").unwrap(), vec![1, -2, 0]);
+
+        for invalid in ["01", "-", "1.", ".5", "1e", "+1", "-x"] {
+            assert!(from_str::<f64>(invalid).is_err(), "{invalid}");
+        }
+        assert!(matches!(
+            from_str::<u32>("1.5").unwrap_err().code(),
+            ErrorCode::ExpectedInteger
+        ));
+        assert!(matches!(
+            from_str::<u32>("x").unwrap_err().code(),
+            ErrorCode::ExpectedInteger
+        ));
+        assert!(matches!(
+            from_str::<u32>("007").unwrap_err().code(),
+            ErrorCode::InvalidNumber
+        ));
+        assert!(matches!(
+            from_str::<f64>("1e").unwrap_err().code(),
+            ErrorCode::Eof
+        ));
+
+        assert!(matches!(
+            from_str::<u32>("4294967296").unwrap_err().code(),
+            ErrorCode::NumberOutOfRange("u32")
+        ));
+        assert!(matches!(
+            from_str::<u64>("-1").unwrap_err().code(),
+            ErrorCode::NumberOutOfRange("u64")
+        ));
+        assert!(matches!(
+            from_str::<f64>("1e400").unwrap_err().code(),
+            ErrorCode::NumberOutOfRange("f64")
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_limits() {
+        // Only a recursive type can follow the input arbitrarily deep.
+        #[derive(Debug)]
+        struct Nested(Vec<Nested>);
+
+        impl<'se> Serializable<'se> for Nested {
+            fn deserialize(deserializer: &mut Deserializer<'se>) -> Result<Self> {
+                Vec::deserialize(deserializer).map(Nested)
+            }
+        }
+
+        let deep = "[".repeat(100_000);
+        assert!(matches!(
+            from_str::<Nested>(&deep).unwrap_err().code(),
+            ErrorCode::RecursionLimitExceeded
+        ));
+        let deep = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert_eq!(from_str::<Nested>(&deep).unwrap().0.len(), 1);
+
+        let limits = Limits {
+            max_depth: 2,
+            max_string_len: 3,
+            max_collection_len: 2,
+        };
+        assert_eq!(
+            from_str_with_limits::<Vec<Vec<u32>>>("[[1],[]]", limits).unwrap(),
+            vec![vec![1], vec![]]
+        );
+        assert!(matches!(
+            from_str_with_limits::<Vec<Vec<Vec<u32>>>>("[[[]]]", limits)
+                .unwrap_err()
+                .code(),
+            ErrorCode::RecursionLimitExceeded
+        ));
+        assert!(matches!(
+            from_str_with_limits::<HashMap<&str, Vec<Vec<u32>>>>(r#"{"a":[[]]}"#, limits)
+                .unwrap_err()
+                .code(),
+            ErrorCode::RecursionLimitExceeded
+        ));
+
+        assert_eq!(
+            from_str_with_limits::<&str>(r#""abc""#, limits).unwrap(),
+            "abc"
+        );
+        assert!(matches!(
+            from_str_with_limits::<&str>(r#""abcd""#, limits)
+                .unwrap_err()
+                .code(),
+            ErrorCode::StringTooLong
+        ));
+
+        assert!(matches!(
+            from_str_with_limits::<Vec<u32>>("[1,2,3]", limits)
+                .unwrap_err()
+                .code(),
+            ErrorCode::CollectionTooLarge
+        ));
+        assert!(matches!(
+            from_str_with_limits::<HashMap<&str, u32>>(r#"{"a":1,"b":2,"c":3}"#, limits)
+                .unwrap_err()
+                .code(),
+            ErrorCode::CollectionTooLarge
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_escapes() {
+        use std::borrow::Cow;
+
+        assert_eq!(
+            from_str::<String>(r#""a\"b\\c\/d\n\t\u0001""#).unwrap(),
+            "a\"b\\c/d\n\t\u{1}"
+        );
+        assert_eq!(
+            from_str::<String>(r#""\u00e9\u20ac\ud83d\ude00""#).unwrap(),
+            "é€😀"
+        );
+        assert_eq!(from_str::<char>(r#""\n""#).unwrap(), '\n');
+        assert_eq!(from_str::<char>(r#""\ud83d\ude00""#).unwrap(), '😀');
+        assert_eq!(
+            from_str::<HashMap<String, u32>>(r#"{"a\u0062": 1}"#).unwrap(),
+            HashMap::from([("ab".to_string(), 1)])
+        );
+
+        // `&str` can only borrow what needs no decoding, `Cow<str>` copies
+        // when it has to.
+        assert_eq!(from_str::<&str>(r#""plain""#).unwrap(), "plain");
+        assert!(matches!(
+            from_str::<&str>(r#""a\nb""#).unwrap_err().code(),
+            ErrorCode::EscapedStr
+        ));
+        assert!(matches!(
+            from_str::<Cow<str>>(r#""plain""#).unwrap(),
+            Cow::Borrowed("plain")
+        ));
+        assert_eq!(from_str::<Cow<str>>(r#""a\nb""#).unwrap(), "a\nb");
+
+        assert!(matches!(
+            from_str::<String>(r#""\x""#).unwrap_err().code(),
+            ErrorCode::InvalidEscape
+        ));
+        assert!(matches!(
+            from_str::<String>(r#""\u12""#).unwrap_err().code(),
+            ErrorCode::InvalidEscape
+        ));
+        assert!(matches!(
+            from_str::<String>(r#""\ud800""#).unwrap_err().code(),
+            ErrorCode::InvalidUnicodeCodePoint
+        ));
+        assert!(matches!(
+            from_str::<String>(r#""\udc00""#).unwrap_err().code(),
+            ErrorCode::InvalidUnicodeCodePoint
+        ));
+        assert!(matches!(
+            from_str::<String>("\"a\nb\"").unwrap_err().code(),
+            ErrorCode::ControlCharacterInString
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_more_types() {
+        use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+        assert_eq!(from_str::<i8>("-128").unwrap(), i8::MIN);
+        assert_eq!(from_str::<u16>("65535").unwrap(), u16::MAX);
+        assert_eq!(from_str::<usize>("7").unwrap(), 7);
+        assert_eq!(
+            from_str::<i128>("-170141183460469231731687303715884105728").unwrap(),
+            i128::MIN
+        );
+        assert!(matches!(
+            from_str::<u8>("256").unwrap_err().code(),
+            ErrorCode::NumberOutOfRange("u8")
+        ));
+        assert_eq!(from_str::<char>(r#""é""#).unwrap(), 'é');
+        assert!(matches!(
+            from_str::<char>(r#""ab""#).unwrap_err().code(),
+            ErrorCode::ExpectedChar
+        ));
+
+        assert_eq!(
+            from_str::<Vec<Option<&str>>>(r#"["a", null]"#).unwrap(),
+            vec![Some("a"), None]
+        );
+        assert!(matches!(
+            from_str::<Option<u32>>("nil").unwrap_err().code(),
+            ErrorCode::ExpectedValue
+        ));
+        assert_eq!(*from_str::<Box<u32>>("1").unwrap(), 1);
+
+        assert_eq!(from_str::<(u32, u32)>("[1, 2]").unwrap(), (1, 2));
+        assert_eq!(
+            from_str::<(i64, &str, bool)>(r#"[-1,"a",true]"#).unwrap(),
+            (-1, "a", true)
+        );
+        assert_eq!(
+            from_str::<(u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8)>(
+                "[1,2,3,4,5,6,7,8,9,10,11,12]"
+            )
+            .unwrap(),
+            (1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12)
+        );
+        assert!(matches!(
+            from_str::<(u32, u32)>("[1]").unwrap_err().code(),
+            ErrorCode::InvalidLength(2)
+        ));
+        assert!(matches!(
+            from_str::<(u32, u32)>("[1,2,3]").unwrap_err().code(),
+            ErrorCode::InvalidLength(2)
+        ));
+
+        assert_eq!(from_str::<[f64; 2]>("[0.5,1]").unwrap(), [0.5, 1.0]);
+        assert!(matches!(
+            from_str::<[u32; 3]>("[1,2]").unwrap_err().code(),
+            ErrorCode::InvalidLength(3)
+        ));
+
+        assert_eq!(
+            from_str::<BTreeMap<&str, u32>>(r#"{"b":2,"a":1}"#).unwrap(),
+            BTreeMap::from([("a", 1), ("b", 2)])
+        );
+        assert_eq!(
+            from_str::<HashSet<u32>>("[1,2,1]").unwrap(),
+            HashSet::from([1, 2])
+        );
+        assert_eq!(
+            from_str::<BTreeSet<&str>>(r#"["b","a"]"#).unwrap(),
+            BTreeSet::from(["a", "b"])
+        );
+        assert_eq!(
+            from_str::<VecDeque<u32>>("[1,2]").unwrap(),
+            VecDeque::from([1, 2])
+        );
+    }
+
+    #[test]
+    fn test_ignored_any() {
+        let json = r#"[{"a": [1, -2.5e3, "x"], "b": {"c": null}}, true, "y"]"#;
+        assert_eq!(from_str::<IgnoredAny>(json).unwrap(), IgnoredAny);
+        assert_eq!(
+            from_str::<HashMap<&str, IgnoredAny>>(r#"{"a":false,"b":[]}"#)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(matches!(
+            from_str::<IgnoredAny>("nul").unwrap_err().code(),
+            ErrorCode::ExpectedValue
+        ));
+        assert!(matches!(
+            from_str::<IgnoredAny>("x").unwrap_err().code(),
+            ErrorCode::ExpectedValue
+        ));
+        assert!(matches!(
+            from_str::<IgnoredAny>("[1").unwrap_err().code(),
+            ErrorCode::Eof
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_with_whitespace() {
+        let expected = HashMap::from([("a", vec![1, 2]), ("b", vec![])]);
+        assert_eq!(
+            from_str::<HashMap<&str, Vec<u32>>>(" {\n  \"a\" : [ 1 ,\t2 ] ,\r\n  \"b\": [ ] \n} ")
+                .unwrap(),
+            expected
+        );
+        assert!(from_str::<bool>("  true").unwrap());
+        assert_eq!(from_str::<&str>(" \" a \"").unwrap(), " a ");
+    }
+
+    #[test]
+    fn test_from_str_at() {
+        let json = r#"{
+            "count": 2,
+            "users": [
+                {"name": "a", "email": "a@example.com", "tags": [[1], {"x": null}]},
+                {"name": "b", "email": "b@example.com"}
+            ],
+            "a/b": {"m~n": true},
+            "": 0
+        }"#;
+        assert_eq!(
+            from_str_at::<&str>(json, "/users/1/email").unwrap(),
+            "b@example.com"
+        );
+        assert_eq!(from_str_at::<u32>(json, "/users/0/tags/0/0").unwrap(), 1);
+        assert_eq!(
+            from_str_at::<Option<u32>>(json, "/users/0/tags/1/x").unwrap(),
+            None
+        );
+        assert!(from_str_at::<bool>(json, "/a~1b/m~0n").unwrap());
+        assert_eq!(
+            from_str_at::<u32>(r#"{"a\/b": {"\u00e9": 7}}"#, "/a~1b/é").unwrap(),
+            7
+        );
+        assert_eq!(from_str_at::<u32>(json, "/").unwrap(), 0);
+        assert!(from_str_at::<u32>("[5]", "").is_err());
+        assert_eq!(from_str_at::<Vec<u32>>("[5]", "").unwrap(), vec![5]);
+
+        let not_found = |pointer| match from_str_at::<IgnoredAny>(json, pointer).unwrap_err().code()
+        {
+            ErrorCode::PointerNotFound(prefix) => prefix.clone(),
+            other => panic!("{pointer}: {other:?}"),
+        };
+        assert_eq!(not_found("/users/2/email"), "/users/2");
+        assert_eq!(not_found("/users/1/phone"), "/users/1/phone");
+        assert_eq!(not_found("/users/01"), "/users/01");
+        assert_eq!(not_found("/users/-"), "/users/-");
+        assert_eq!(not_found("/count/0"), "/count/0");
+        assert!(matches!(
+            from_str_at::<IgnoredAny>(json, "users").unwrap_err().code(),
+            ErrorCode::InvalidPointer(_)
+        ));
+        assert!(matches!(
+            from_str_at::<IgnoredAny>(json, "/a~2b").unwrap_err().code(),
+            ErrorCode::InvalidPointer(_)
+        ));
+        // Skipped values still have to be well-formed.
+        assert!(matches!(
+            from_str_at::<u32>(r#"{"a": [1 2], "b": 3}"#, "/b")
+                .unwrap_err()
+                .code(),
+            ErrorCode::ExpectedArrayComma
+        ));
+    }
+
+    #[test]
+    fn test_trailing_input() {
+        assert_eq!(
+            from_str::<HashMap<&str, u32>>("{\"a\":1} \n").unwrap(),
+            HashMap::from([("a", 1)])
+        );
+        let err = from_str::<HashMap<&str, u32>>(r#"{"a":1}garbage"#).unwrap_err();
+        assert!(matches!(err.code(), ErrorCode::TrailingCharacters));
+        assert_eq!(err.column(), Some(8));
+        assert!(matches!(
+            from_str::<u32>("1 2").unwrap_err().code(),
+            ErrorCode::TrailingCharacters
+        ));
+        assert!(matches!(
+            from_str::<Vec<u32>>("[1]]").unwrap_err().code(),
+            ErrorCode::TrailingCharacters
+        ));
+
+        // A deserializer built by hand only checks when asked to.
+        let mut deserializer = Deserializer::new("1 2");
+        assert_eq!(u32::deserialize(&mut deserializer).unwrap(), 1);
+        assert!(deserializer.end().is_err());
+        let mut deserializer = Deserializer::new("[1, 2] ");
+        assert_eq!(Vec::<u32>::deserialize(&mut deserializer).unwrap(), [1, 2]);
+        assert!(deserializer.end().is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        use crate::desert::DuplicateKeys;
+        use std::collections::BTreeMap;
+
+        let json = r#"{"a": 1, "b": 2, "a": [3]}"#;
+        let err = from_str::<HashMap<&str, IgnoredAny>>(json).unwrap_err();
+        assert!(matches!(err.code(), ErrorCode::DuplicateKey));
+        assert_eq!(err.snippet(), r#""a": [3]}"#);
+
+        let read = |policy| {
+            let mut deserializer =
+                Deserializer::new(r#"{"a": 1, "b": 2, "a": 3}"#).duplicate_keys(policy);
+            BTreeMap::<&str, u32>::deserialize(&mut deserializer)
+        };
+        assert!(read(DuplicateKeys::Error).is_err());
+        assert_eq!(
+            read(DuplicateKeys::FirstWins).unwrap(),
+            BTreeMap::from([("a", 1), ("b", 2)])
+        );
+        assert_eq!(
+            read(DuplicateKeys::LastWins).unwrap(),
+            BTreeMap::from([("a", 3), ("b", 2)])
+        );
+
+        // Values that lose are skipped without being read as `V`.
+        let mut deserializer = Deserializer::new(json).duplicate_keys(DuplicateKeys::FirstWins);
+        assert_eq!(
+            HashMap::<&str, u32>::deserialize(&mut deserializer).unwrap(),
+            HashMap::from([("a", 1), ("b", 2)])
+        );
+    }
 }
@@ -1,26 +1,266 @@
-use std::fmt::{Display, Formatter};
+use std::fmt::{self, Display, Formatter};
+use std::io;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+// How much of the input after an error is kept to show where it happened, in
+// characters.
+const SNIPPET_LEN: usize = 32;
+
+// The error is boxed so that `Result<T>` stays small on the happy path.
+#[derive(Debug)]
+pub struct Error {
+    err: Box<ErrorImpl>,
+}
+
+#[derive(Debug)]
+struct ErrorImpl {
+    code: ErrorCode,
+    position: Option<Position>,
+    // The input from the position on, cut short.
+    snippet: String,
+}
+
+// Where in the input an error was detected. `offset` counts bytes from the
+// start of the input, `line` and `column` are 1-based and count characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    // The position `offset` bytes into `input`.
+    pub(crate) fn in_str(input: &str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
 #[derive(Debug)]
-pub enum Error {
+pub enum ErrorCode {
+    Io(io::Error),
     Eof,
     ExpectedBoolean,
     ExpectedInteger,
+    ExpectedFloat,
+    InvalidNumber,
+    NumberOutOfRange(&'static str),
     ExpectedString,
+    InvalidEscape,
+    InvalidUnicodeCodePoint,
+    ControlCharacterInString,
+    EscapedStr,
+    InvalidUtf8,
+    ExpectedChar,
     ExpectedArray,
     ExpectedArrayComma,
     ExpectedArrayEnd,
+    InvalidLength(usize),
     ExpectedMap,
     ExpectedMapComma,
     ExpectedMapColon,
-    ExpectedMapEnd
+    ExpectedMapEnd,
+    RecursionLimitExceeded,
+    StringTooLong,
+    CollectionTooLarge,
+    ExpectedValue,
+    TrailingCharacters,
+    DuplicateKey,
+    MissingField(&'static str),
+    DuplicateField(&'static str),
+    UnknownField(String),
+    UnknownVariant(String),
+    InvalidPointer(String),
+    PointerNotFound(String),
+    FloatMustBeFinite,
+}
+
+impl Error {
+    pub fn code(&self) -> &ErrorCode {
+        &self.err.code
+    }
+
+    // Where the error was detected, if it came from reading input.
+    pub fn position(&self) -> Option<Position> {
+        self.err.position
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.position().map(|position| position.line)
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        self.position().map(|position| position.column)
+    }
+
+    // The start of the input from where the error was detected, empty at the
+    // end of the input or for errors without a position.
+    pub fn snippet(&self) -> &str {
+        &self.err.snippet
+    }
+
+    // Errors are created without a position, wherever they come from, and
+    // given one on their way out of the tokenizer or deserializer. Only the
+    // first position sticks, it is the most precise.
+    pub(crate) fn locate(mut self, position: Position, rest: &str) -> Self {
+        if self.err.position.is_none() {
+            self.err.position = Some(position);
+            self.err.snippet = rest.chars().take(SNIPPET_LEN).collect();
+        }
+        self
+    }
+
+    pub(crate) fn set_code(&mut self, code: ErrorCode) {
+        self.err.code = code;
+    }
+}
+
+impl From<ErrorCode> for Error {
+    fn from(code: ErrorCode) -> Self {
+        Error {
+            err: Box::new(ErrorImpl {
+                code,
+                position: None,
+                snippet: String::new(),
+            }),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        ErrorCode::Io(err).into()
+    }
 }
 
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::Io(err) => write!(f, "io error: {err}"),
+            ErrorCode::Eof => write!(f, "unexpected end of input"),
+            ErrorCode::ExpectedBoolean => write!(f, "expected `true` or `false`"),
+            ErrorCode::ExpectedInteger => write!(f, "expected integer"),
+            ErrorCode::ExpectedFloat => write!(f, "expected number"),
+            ErrorCode::InvalidNumber => write!(f, "invalid number"),
+            ErrorCode::NumberOutOfRange(ty) => write!(f, "number out of range for `{ty}`"),
+            ErrorCode::ExpectedString => write!(f, "expected string"),
+            ErrorCode::InvalidEscape => write!(f, "invalid escape sequence"),
+            ErrorCode::InvalidUnicodeCodePoint => write!(f, "invalid unicode code point"),
+            ErrorCode::ControlCharacterInString => {
+                write!(f, "control character in string must be escaped")
+            }
+            ErrorCode::EscapedStr => {
+                write!(f, "a string with escapes cannot be borrowed as `&str`")
+            }
+            ErrorCode::InvalidUtf8 => write!(f, "input is not valid UTF-8"),
+            ErrorCode::ExpectedChar => write!(f, "expected a string of exactly one character"),
+            ErrorCode::ExpectedArray => write!(f, "expected `[`"),
+            ErrorCode::ExpectedArrayComma => write!(f, "expected `,` or `]` in array"),
+            ErrorCode::ExpectedArrayEnd => write!(f, "expected `]`"),
+            ErrorCode::InvalidLength(len) => write!(f, "expected an array of {len} elements"),
+            ErrorCode::ExpectedMap => write!(f, "expected `{{`"),
+            ErrorCode::ExpectedMapComma => write!(f, "expected `,` or `}}` in object"),
+            ErrorCode::ExpectedMapColon => write!(f, "expected `:` after object key"),
+            ErrorCode::ExpectedMapEnd => write!(f, "expected `}}`"),
+            ErrorCode::RecursionLimitExceeded => write!(f, "recursion limit exceeded"),
+            ErrorCode::StringTooLong => write!(f, "string longer than the limit"),
+            ErrorCode::CollectionTooLarge => write!(f, "too many elements in array or object"),
+            ErrorCode::ExpectedValue => write!(f, "expected value"),
+            ErrorCode::TrailingCharacters => write!(f, "trailing characters after the value"),
+            ErrorCode::DuplicateKey => write!(f, "duplicate key in object"),
+            ErrorCode::MissingField(field) => write!(f, "missing field `{field}`"),
+            ErrorCode::DuplicateField(field) => write!(f, "duplicate field `{field}`"),
+            ErrorCode::UnknownField(field) => write!(f, "unknown field `{field}`"),
+            ErrorCode::UnknownVariant(variant) => write!(f, "unknown variant `{variant}`"),
+            ErrorCode::InvalidPointer(pointer) => write!(f, "invalid JSON pointer `{pointer}`"),
+            ErrorCode::PointerNotFound(pointer) => write!(f, "no value at `{pointer}`"),
+            ErrorCode::FloatMustBeFinite => {
+                write!(f, "NaN and infinity cannot be represented in JSON")
+            }
+        }
+    }
+}
+
+// Renders as, for example:
+//
+//   expected `,` or `}` in object at line 2 column 2 (byte 9), near "\"b\": 2}"
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error")
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.err.code)?;
+        let Some(Position {
+            offset,
+            line,
+            column,
+        }) = self.err.position
+        else {
+            return Ok(());
+        };
+        write!(f, " at line {line} column {column} (byte {offset})")?;
+        if !self.err.snippet.is_empty() {
+            write!(f, ", near {:?}", self.err.snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.err.code {
+            ErrorCode::Io(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
-impl std::error::Error for Error {}
+#[cfg(test)]
+mod tests {
+    use crate::error::Position;
+    use crate::{from_str, to_string};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_error_display() {
+        let err = from_str::<HashMap<&str, u32>>("{\"a\": 1\n \"b\": 2}").unwrap_err();
+        assert_eq!(
+            err.position(),
+            Some(Position {
+                offset: 9,
+                line: 2,
+                column: 2
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            r#"expected `,` or `}` in object at line 2 column 2 (byte 9), near "\"b\": 2}""#
+        );
+
+        // Errors from `Serializable` impls point at the token they are about.
+        assert_eq!(
+            from_str::<Vec<u8>>("[1, 300]").unwrap_err().to_string(),
+            r#"number out of range for `u8` at line 1 column 5 (byte 4), near "300]""#
+        );
+        assert_eq!(
+            from_str::<Vec<u32>>("[1,").unwrap_err().to_string(),
+            "unexpected end of input at line 1 column 4 (byte 3)"
+        );
+        let long = format!("[\"éé\", {}]", "x".repeat(100));
+        let err = from_str::<Vec<&str>>(&long).unwrap_err();
+        assert_eq!((err.line(), err.column()), (Some(1), Some(8)));
+        assert_eq!(err.position().unwrap().offset, 9);
+        assert_eq!(err.snippet().len(), 32);
+
+        let err = to_string(&f64::NAN).unwrap_err();
+        assert_eq!(err.position(), None);
+        assert_eq!(
+            err.to_string(),
+            "NaN and infinity cannot be represented in JSON"
+        );
+    }
+}
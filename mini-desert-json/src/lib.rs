@@ -1,5 +1,17 @@
 mod desert;
 mod error;
+mod ser;
+mod token;
 
-pub use desert::from_str;
-pub use error::{Error, Result};
+pub use desert::{
+    Deserializer, DuplicateKeys, IgnoredAny, Limits, Serializable, from_str, from_str_at,
+    from_str_with_limits,
+};
+pub use error::{Error, ErrorCode, Position, Result};
+pub use ser::{Array, Encodable, Object, Serializer, to_string, to_writer};
+pub use token::{ReaderTokenizer, Token, Tokenizer, unescape};
+
+// The derive macros live in the macro namespace, so they share their names
+// with the traits the same way serde's derives do.
+#[cfg(feature = "derive")]
+pub use mini_desert_json_derive::{Encodable, Serializable};
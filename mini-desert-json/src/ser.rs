@@ -0,0 +1,428 @@
+use crate::error::{ErrorCode, Result};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Display};
+use std::io;
+
+pub fn to_string<T: ?Sized + Encodable>(value: &T) -> Result<String> {
+    let mut writer = Vec::new();
+    to_writer(&mut writer, value)?;
+    // Everything written comes from `&str`s or ASCII punctuation.
+    Ok(String::from_utf8(writer).expect("serializer output is valid UTF-8"))
+}
+
+// Write `value` as compact JSON. Nothing is buffered, so a `File` or socket
+// should be wrapped in an `io::BufWriter`.
+pub fn to_writer<W: io::Write, T: ?Sized + Encodable>(writer: W, value: &T) -> Result<()> {
+    value.encode(&mut Serializer::new(writer))
+}
+
+// The writing half of `Serializable`. A type that implements both can be
+// written out and read back in:
+//
+//     let text = to_string(&servers)?;
+//     let again: Vec<Server> = from_str(&text)?;
+//
+// Strings are written with the escapes JSON requires and decoded again on the
+// way back in. Only `&str` cannot be read back if the string needed escaping,
+// it has nowhere to keep the decoded text.
+pub trait Encodable {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()>;
+}
+
+pub struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: io::Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write_null(&mut self) -> Result<()> {
+        self.writer.write_all(b"null")?;
+        Ok(())
+    }
+
+    pub fn write_bool(&mut self, v: bool) -> Result<()> {
+        self.writer.write_all(if v { b"true" } else { b"false" })?;
+        Ok(())
+    }
+
+    pub fn write_integer<T: Display>(&mut self, v: T) -> Result<()> {
+        write!(self.writer, "{v}")?;
+        Ok(())
+    }
+
+    // The shortest text that reads back as the same float. JSON has no NaN or
+    // infinity, so those are an error rather than invalid output.
+    pub fn write_float<T: Into<f64> + Debug + Copy>(&mut self, v: T) -> Result<()> {
+        if !v.into().is_finite() {
+            return Err(ErrorCode::FloatMustBeFinite.into());
+        }
+        write!(self.writer, "{v:?}")?;
+        Ok(())
+    }
+
+    // Write `v` in quotes with the escaping RFC 8259 requires: quotation mark,
+    // reverse solidus and the control characters U+0000 to U+001F.
+    pub fn write_str(&mut self, v: &str) -> Result<()> {
+        self.writer.write_all(b"\"")?;
+        let mut start = 0;
+        for (i, ch) in v.char_indices() {
+            let escape = match ch {
+                '"' => "\\\"",
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                '\u{0}'..='\u{1F}' => {
+                    self.writer.write_all(&v.as_bytes()[start..i])?;
+                    write!(self.writer, "\\u{:04x}", ch as u32)?;
+                    start = i + 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            self.writer.write_all(&v.as_bytes()[start..i])?;
+            self.writer.write_all(escape.as_bytes())?;
+            start = i + 1;
+        }
+        self.writer.write_all(&v.as_bytes()[start..])?;
+        self.writer.write_all(b"\"")?;
+        Ok(())
+    }
+
+    // Start an array, the elements are written through the returned `Array`.
+    pub fn write_array(&mut self) -> Result<Array<'_, W>> {
+        self.writer.write_all(b"[")?;
+        Ok(Array {
+            serializer: self,
+            first: true,
+        })
+    }
+
+    // Start an object, the entries are written through the returned `Object`.
+    pub fn write_object(&mut self) -> Result<Object<'_, W>> {
+        self.writer.write_all(b"{")?;
+        Ok(Object {
+            serializer: self,
+            first: true,
+        })
+    }
+}
+
+// An array being written. `end` has to be called to close it.
+pub struct Array<'a, W> {
+    serializer: &'a mut Serializer<W>,
+    first: bool,
+}
+
+impl<W: io::Write> Array<'_, W> {
+    pub fn element<T: ?Sized + Encodable>(&mut self, value: &T) -> Result<()> {
+        if !self.first {
+            self.serializer.writer.write_all(b",")?;
+        }
+        self.first = false;
+        value.encode(self.serializer)
+    }
+
+    pub fn end(self) -> Result<()> {
+        self.serializer.writer.write_all(b"]")?;
+        Ok(())
+    }
+}
+
+// An object being written. `end` has to be called to close it.
+pub struct Object<'a, W> {
+    serializer: &'a mut Serializer<W>,
+    first: bool,
+}
+
+impl<W: io::Write> Object<'_, W> {
+    pub fn entry<T: ?Sized + Encodable>(&mut self, key: &str, value: &T) -> Result<()> {
+        if !self.first {
+            self.serializer.writer.write_all(b",")?;
+        }
+        self.first = false;
+        self.serializer.write_str(key)?;
+        self.serializer.writer.write_all(b":")?;
+        value.encode(self.serializer)
+    }
+
+    pub fn end(self) -> Result<()> {
+        self.serializer.writer.write_all(b"}")?;
+        Ok(())
+    }
+}
+
+impl<T: ?Sized + Encodable> Encodable for &T {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        (**self).encode(serializer)
+    }
+}
+
+impl Encodable for str {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_str(self)
+    }
+}
+
+impl Encodable for String {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_str(self)
+    }
+}
+
+impl Encodable for Cow<'_, str> {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_str(self)
+    }
+}
+
+impl Encodable for bool {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_bool(*self)
+    }
+}
+
+macro_rules! impl_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl Encodable for $ty {
+                fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+                    serializer.write_integer(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+impl Encodable for f32 {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_float(*self)
+    }
+}
+
+impl Encodable for f64 {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_float(*self)
+    }
+}
+
+impl Encodable for char {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.write_str(self.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl<T: Encodable> Encodable for Option<T> {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        match self {
+            Some(value) => value.encode(serializer),
+            None => serializer.write_null(),
+        }
+    }
+}
+
+impl<T: ?Sized + Encodable> Encodable for Box<T> {
+    fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        (**self).encode(serializer)
+    }
+}
+
+// Object keys are strings in JSON, so only maps with string keys can be
+// written. The entries come in the map's iteration order.
+macro_rules! impl_map {
+    ($($ty:ident)*) => {
+        $(
+            impl<K, V> Encodable for $ty<K, V>
+            where
+                K: AsRef<str>,
+                V: Encodable,
+            {
+                fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+                    let mut object = serializer.write_object()?;
+                    for (key, value) in self {
+                        object.entry(key.as_ref(), value)?;
+                    }
+                    object.end()
+                }
+            }
+        )*
+    };
+}
+
+impl_map!(HashMap BTreeMap);
+
+// Everything that can be iterated over is written as an array.
+macro_rules! impl_sequence {
+    ($($ty:ty $(, const $n:ident)?;)*) => {
+        $(
+            impl<T: Encodable $(, const $n: usize)?> Encodable for $ty {
+                fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+                    let mut array = serializer.write_array()?;
+                    for element in self {
+                        array.element(element)?;
+                    }
+                    array.end()
+                }
+            }
+        )*
+    };
+}
+
+impl_sequence! {
+    [T];
+    [T; N], const N;
+    Vec<T>;
+    VecDeque<T>;
+    HashSet<T>;
+    BTreeSet<T>;
+}
+
+// Tuples are arrays with one element per field.
+macro_rules! impl_tuple {
+    ($($idx:tt $ty:ident)+) => {
+        impl<$($ty: Encodable),+> Encodable for ($($ty,)+) {
+            fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+                let mut array = serializer.write_array()?;
+                $(array.element(&self.$idx)?;)+
+                array.end()
+            }
+        }
+    };
+}
+
+impl_tuple!(0 T0);
+impl_tuple!(0 T0 1 T1);
+impl_tuple!(0 T0 1 T1 2 T2);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10);
+impl_tuple!(0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11);
+
+#[cfg(test)]
+mod tests {
+    use crate::desert::from_str;
+    use crate::error::ErrorCode;
+    use crate::ser::{Encodable, Serializer, to_string, to_writer};
+    use std::collections::HashMap;
+    use std::io;
+
+    #[test]
+    fn test_serialize_primitives() {
+        assert_eq!(
+            to_string("a\"b\\c\n\u{1}é").unwrap(),
+            r#""a\"b\\c\n\u0001é""#
+        );
+        assert_eq!(to_string(&true).unwrap(), "true");
+        assert_eq!(to_string(&u64::MAX).unwrap(), "18446744073709551615");
+        assert_eq!(to_string(&i32::MIN).unwrap(), "-2147483648");
+        assert_eq!(to_string(&0.1f32).unwrap(), "0.1");
+        assert_eq!(to_string(&1e100).unwrap(), "1e100");
+        assert!(matches!(
+            to_string(&f64::NAN).unwrap_err().code(),
+            ErrorCode::FloatMustBeFinite
+        ));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let map = HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![])]);
+        let json = to_string(&map).unwrap();
+        assert_eq!(from_str::<HashMap<String, Vec<u32>>>(&json).unwrap(), map);
+
+        let nested = vec![vec![-1.5, 2.0], vec![]];
+        assert_eq!(to_string(&nested).unwrap(), "[[-1.5,2.0],[]]");
+        assert_eq!(
+            from_str::<Vec<Vec<f64>>>(&to_string(&nested).unwrap()).unwrap(),
+            nested
+        );
+
+        let strs: HashMap<&str, &str> = HashMap::from([("k", "v")]);
+        assert_eq!(to_string(&strs).unwrap(), r#"{"k":"v"}"#);
+
+        // Escaped on the way out, decoded on the way back in.
+        let escaped = vec![
+            "\"quoted\"".to_string(),
+            "back\\slash".to_string(),
+            "two\nlines\r\t".to_string(),
+            "\u{1}\u{1f}\u{7f}".to_string(),
+            "é😀".to_string(),
+        ];
+        let json = to_string(&escaped).unwrap();
+        assert_eq!(from_str::<Vec<String>>(&json).unwrap(), escaped);
+        let keys = HashMap::from([("\"k\\\n".to_string(), '\u{0}')]);
+        let json = to_string(&keys).unwrap();
+        assert_eq!(from_str::<HashMap<String, char>>(&json).unwrap(), keys);
+    }
+
+    #[test]
+    fn test_serialize_more_types() {
+        use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+        assert_eq!(to_string(&i8::MIN).unwrap(), "-128");
+        assert_eq!(to_string(&u128::MAX).unwrap(), u128::MAX.to_string());
+        assert_eq!(to_string(&'"').unwrap(), r#""\"""#);
+        assert_eq!(to_string(&[Some(1), None]).unwrap(), "[1,null]");
+        assert_eq!(to_string(&Box::new("a")).unwrap(), r#""a""#);
+        assert_eq!(to_string(&(1, "a", (true,))).unwrap(), r#"[1,"a",[true]]"#);
+        assert_eq!(to_string(&[[0u8; 2]; 2]).unwrap(), "[[0,0],[0,0]]");
+        assert_eq!(
+            to_string(&BTreeMap::from([("b", 2), ("a", 1)])).unwrap(),
+            r#"{"a":1,"b":2}"#
+        );
+        assert_eq!(
+            to_string(&BTreeSet::from(['b', 'a'])).unwrap(),
+            r#"["a","b"]"#
+        );
+        assert_eq!(to_string(&VecDeque::from([1, 2])).unwrap(), "[1,2]");
+
+        let record = (
+            1_700_000_000i64,
+            0.25f64,
+            Some("ok".to_string()),
+            (3u32, 4u32),
+        );
+        let json = to_string(&record).unwrap();
+        assert_eq!(json, r#"[1700000000,0.25,"ok",[3,4]]"#);
+        assert_eq!(
+            from_str::<(i64, f64, Option<String>, (u32, u32))>(&json).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn test_serialize_custom_impl() {
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        // What `#[derive(Encodable)]` generates.
+        impl Encodable for Point {
+            fn encode<W: io::Write>(&self, serializer: &mut Serializer<W>) -> crate::Result<()> {
+                let mut object = serializer.write_object()?;
+                object.entry("x", &self.x)?;
+                object.entry("y", &self.y)?;
+                object.end()
+            }
+        }
+
+        let mut writer = Vec::new();
+        to_writer(&mut writer, &[Point { x: 1, y: -2 }][..]).unwrap();
+        assert_eq!(writer, br#"[{"x":1,"y":-2}]"#);
+    }
+}
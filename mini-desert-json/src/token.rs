@@ -0,0 +1,1087 @@
+use crate::desert::Limits;
+use crate::error::{Error, ErrorCode, Position, Result};
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader};
+
+// One step through a JSON document. A document is a single value, and values
+// come out in the order they appear:
+//
+//     {"a": [1, true]}
+//
+// is `StartObject`, `Key("a")`, `StartArray`, `Number("1")`, `Bool(true)`,
+// `EndArray`, `EndObject`. The tokenizer checks the punctuation in between
+// and the tokens themselves, so a sequence of tokens always belongs to
+// well-formed JSON.
+//
+// Strings and numbers are handed out as the text between the quotes or of the
+// number itself. Escape sequences have been checked but are not decoded,
+// `unescape` does that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(&'a str),
+    String(&'a str),
+    Number(&'a str),
+    Bool(bool),
+    Null,
+}
+
+// Decode the escape sequences in the text of a `Token::String` or
+// `Token::Key`. The text is only copied if it has any.
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        let (ch, len) = decode_escape(&rest[i + 1..]);
+        unescaped.push(ch);
+        rest = &rest[i + 1 + len..];
+    }
+    unescaped.push_str(rest);
+    Cow::Owned(unescaped)
+}
+
+// The character an escape stands for, given the text after the backslash, and
+// how much of that text the escape takes up. A surrogate pair is a single
+// escape. The tokenizer never hands out malformed escapes, but text from
+// elsewhere can have them, they come out as U+FFFD.
+fn decode_escape(escape: &str) -> (char, usize) {
+    let hex = |at: usize| {
+        let digits = escape.get(at..at + 4)?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(digits, 16).ok()
+    };
+    let ch = match escape.chars().next() {
+        Some('"') => '"',
+        Some('\\') => '\\',
+        Some('/') => '/',
+        Some('b') => '\u{8}',
+        Some('f') => '\u{c}',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('t') => '\t',
+        Some('u') => {
+            return match hex(1) {
+                Some(high @ 0xD800..=0xDBFF) => match (escape.get(5..7), hex(7)) {
+                    (Some("\\u"), Some(low @ 0xDC00..=0xDFFF)) => {
+                        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                        (
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+                            11,
+                        )
+                    }
+                    _ => (char::REPLACEMENT_CHARACTER, 5),
+                },
+                // A lone low surrogate is not a `char` either.
+                Some(code) => (
+                    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+                    5,
+                ),
+                None => (char::REPLACEMENT_CHARACTER, 1),
+            };
+        }
+        Some(other) => return (char::REPLACEMENT_CHARACTER, other.len_utf8()),
+        None => return (char::REPLACEMENT_CHARACTER, 0),
+    };
+    (ch, 1)
+}
+
+// Reads the tokens of a document out of a `&str`, without allocating. The
+// text of strings and numbers is borrowed from the input.
+//
+// To pick one field out of a large document, walk to it and skip everything
+// else with `skip_value`:
+//
+//     let mut tokenizer = Tokenizer::new(input);
+//     tokenizer.next_token()?; // StartObject
+//     while let Some(Token::Key(key)) = tokenizer.next_token()? {
+//         if key == "id" {
+//             return Ok(tokenizer.next_token()?);
+//         }
+//         tokenizer.skip_value()?;
+//     }
+pub struct Tokenizer<'de> {
+    source: StrSource<'de>,
+    machine: Machine,
+}
+
+impl<'de> Tokenizer<'de> {
+    pub fn new(input: &'de str) -> Self {
+        Tokenizer::with_limits(input, Limits::default())
+    }
+
+    pub fn with_limits(input: &'de str, limits: Limits) -> Self {
+        Tokenizer {
+            source: StrSource { input, pos: 0 },
+            machine: Machine::new(limits),
+        }
+    }
+
+    // The next token, or `None` once the whole value has been read. Anything
+    // after the value is not looked at.
+    pub fn next_token(&mut self) -> Result<Option<Token<'de>>> {
+        Ok(step(&mut self.source, &mut self.machine)?.map(Raw::token))
+    }
+
+    // Skip the next value, with everything nested in it. In an object this
+    // can be the key as well, which skips only the key.
+    pub fn skip_value(&mut self) -> Result<()> {
+        let depth = self.depth();
+        loop {
+            self.next_token()?.ok_or(ErrorCode::Eof)?;
+            if self.machine.skipped(depth)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // How many arrays and objects the tokenizer is inside of.
+    pub fn depth(&self) -> usize {
+        self.machine.stack.len()
+    }
+
+    // Check that nothing but whitespace follows the value, once it has been
+    // read.
+    pub fn end(&mut self) -> Result<()> {
+        end(&mut self.source, &self.machine)
+    }
+
+    // Give an error found while looking at the last token the position of
+    // that token.
+    pub(crate) fn locate_token(&self, err: Error) -> Error {
+        let offset = self.machine.token_start;
+        let input = self.source.input;
+        err.locate(Position::in_str(input, offset), &input[offset..])
+    }
+}
+
+// The same as `Tokenizer` but reading the document from an `io::Read`, a
+// little at a time, so it never has to be in memory as a whole. The text of
+// a string or number only lives until the next call to `next_token`.
+pub struct ReaderTokenizer<R> {
+    source: IoSource<R>,
+    machine: Machine,
+}
+
+impl<R: io::Read> ReaderTokenizer<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderTokenizer::with_limits(reader, Limits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: Limits) -> Self {
+        ReaderTokenizer {
+            source: IoSource {
+                reader: BufReader::new(reader),
+                scratch: Vec::new(),
+                position: Position {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                },
+            },
+            machine: Machine::new(limits),
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Option<Token<'_>>> {
+        Ok(step(&mut self.source, &mut self.machine)?.map(Raw::token))
+    }
+
+    pub fn skip_value(&mut self) -> Result<()> {
+        let depth = self.depth();
+        loop {
+            self.next_token()?.ok_or(ErrorCode::Eof)?;
+            if self.machine.skipped(depth)? {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.machine.stack.len()
+    }
+
+    // Reads up to the end of the input, as a document ends where its value
+    // does. To read several documents from one stream, don't call this.
+    pub fn end(&mut self) -> Result<()> {
+        end(&mut self.source, &self.machine)
+    }
+}
+
+// What comes next, given what came before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // A value: at the start of the document or after a `:`.
+    Value,
+    // After `[`, a value or `]`.
+    ArrayStart,
+    // After an element, `,` or `]`.
+    ArrayNext,
+    // After `{`, a key or `}`.
+    ObjectStart,
+    // After the value of an entry, `,` or `}`.
+    ObjectNext,
+    // After a key.
+    Colon,
+    // The whole value has been read.
+    Done,
+}
+
+// An array or object that has been opened and not yet closed.
+struct Frame {
+    object: bool,
+    // Elements or entries so far.
+    len: usize,
+}
+
+// The part of tokenizing that does not depend on where the input comes from:
+// which token may come next and the limits.
+struct Machine {
+    stack: Vec<Frame>,
+    state: State,
+    limits: Limits,
+    // The offset of the last token.
+    token_start: usize,
+}
+
+impl Machine {
+    fn new(limits: Limits) -> Self {
+        Machine {
+            stack: Vec::new(),
+            state: State::Value,
+            limits,
+            token_start: 0,
+        }
+    }
+
+    fn open(&mut self, object: bool) -> Result<()> {
+        if self.stack.len() == self.limits.max_depth {
+            return Err(ErrorCode::RecursionLimitExceeded.into());
+        }
+        self.stack.push(Frame { object, len: 0 });
+        self.state = if object {
+            State::ObjectStart
+        } else {
+            State::ArrayStart
+        };
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.stack.pop();
+        self.after_value();
+    }
+
+    // Called before each element or entry.
+    fn count(&mut self) -> Result<()> {
+        let frame = self.stack.last_mut().expect("inside an array or object");
+        if frame.len == self.limits.max_collection_len {
+            return Err(ErrorCode::CollectionTooLarge.into());
+        }
+        frame.len += 1;
+        Ok(())
+    }
+
+    fn after_value(&mut self) {
+        self.state = match self.stack.last() {
+            None => State::Done,
+            Some(frame) if frame.object => State::ObjectNext,
+            Some(_) => State::ArrayNext,
+        };
+    }
+
+    // Whether `skip_value`, started at `depth`, is done after a token. Going
+    // up means the token closed the container rather than being a value.
+    fn skipped(&self, depth: usize) -> Result<bool> {
+        match self.stack.len() {
+            len if len < depth => Err(ErrorCode::ExpectedValue.into()),
+            len => Ok(len == depth),
+        }
+    }
+}
+
+// A token whose text has not been given its final lifetime yet, which
+// differs between the sources.
+enum Raw<T> {
+    Token(Token<'static>),
+    Key(T),
+    String(T),
+    Number(T),
+}
+
+impl<'a> Raw<&'a str> {
+    fn token(self) -> Token<'a> {
+        match self {
+            Raw::Token(token) => token,
+            Raw::Key(text) => Token::Key(text),
+            Raw::String(text) => Token::String(text),
+            Raw::Number(text) => Token::Number(text),
+        }
+    }
+}
+
+// Where the tokenizer gets its bytes from.
+trait Source {
+    // The text of a string or number. Borrowed from the input if there is
+    // one, or from a buffer in the source.
+    type Text<'s>
+    where
+        Self: 's;
+
+    fn peek(&mut self) -> Result<Option<u8>>;
+
+    // Consume the byte returned by `peek`.
+    fn discard(&mut self);
+
+    // Consume the byte returned by `peek` as part of the text of a string or
+    // number.
+    fn take(&mut self) {
+        self.discard();
+    }
+
+    // How many bytes have been consumed.
+    fn offset(&self) -> usize;
+
+    // Give `err` the position of the next byte. `string` and `number` do this
+    // for their own errors, everything else is up to the caller.
+    fn locate(&self, err: Error) -> Error;
+
+    // The rest of a string, after the opening quote.
+    fn string(&mut self, max_len: usize) -> Result<Self::Text<'_>>;
+
+    fn number(&mut self) -> Result<Self::Text<'_>>;
+
+    // Insignificant whitespace may appear around any structural character or
+    // value, but never inside a token.
+    fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.discard();
+        }
+        Ok(())
+    }
+
+    fn peek_token(&mut self) -> Result<u8> {
+        self.skip_whitespace()?;
+        match self.peek()? {
+            Some(b) => Ok(b),
+            None => Err(self.locate(ErrorCode::Eof.into())),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &[u8]) -> Result<()> {
+        for &expected in keyword {
+            if self.peek()? != Some(expected) {
+                return Err(self.locate(ErrorCode::ExpectedValue.into()));
+            }
+            self.discard();
+        }
+        Ok(())
+    }
+}
+
+// Read the next token from `source`, checking it against what `machine`
+// expects.
+fn step<'s, S: Source>(
+    source: &'s mut S,
+    machine: &mut Machine,
+) -> Result<Option<Raw<S::Text<'s>>>> {
+    if machine.state == State::Done {
+        return Ok(None);
+    }
+    let mut next = source.peek_token()?;
+    machine.token_start = source.offset();
+
+    // The punctuation before the next key or value, or the end of the array
+    // or object.
+    match machine.state {
+        State::ArrayStart | State::ArrayNext if next == b']' => {
+            source.discard();
+            machine.close();
+            return Ok(Some(Raw::Token(Token::EndArray)));
+        }
+        State::ObjectStart | State::ObjectNext if next == b'}' => {
+            source.discard();
+            machine.close();
+            return Ok(Some(Raw::Token(Token::EndObject)));
+        }
+        State::ArrayNext | State::ObjectNext | State::Colon => {
+            let (expected, error) = match machine.state {
+                State::ArrayNext => (b',', ErrorCode::ExpectedArrayComma),
+                State::ObjectNext => (b',', ErrorCode::ExpectedMapComma),
+                _ => (b':', ErrorCode::ExpectedMapColon),
+            };
+            if next != expected {
+                return Err(source.locate(error.into()));
+            }
+            source.discard();
+            next = source.peek_token()?;
+            machine.token_start = source.offset();
+        }
+        _ => {}
+    }
+
+    match machine.state {
+        State::ObjectStart | State::ObjectNext => {
+            // Object keys are always strings.
+            if next != b'"' {
+                return Err(source.locate(ErrorCode::ExpectedString.into()));
+            }
+            machine.count().map_err(|err| source.locate(err))?;
+            source.discard();
+            machine.state = State::Colon;
+            let max_len = machine.limits.max_string_len;
+            return Ok(Some(Raw::Key(source.string(max_len)?)));
+        }
+        State::ArrayStart | State::ArrayNext => {
+            machine.count().map_err(|err| source.locate(err))?;
+        }
+        _ => {}
+    }
+
+    let raw = match next {
+        b'{' | b'[' => {
+            machine
+                .open(next == b'{')
+                .map_err(|err| source.locate(err))?;
+            source.discard();
+            return Ok(Some(Raw::Token(if next == b'{' {
+                Token::StartObject
+            } else {
+                Token::StartArray
+            })));
+        }
+        b'"' => {
+            source.discard();
+            machine.after_value();
+            let max_len = machine.limits.max_string_len;
+            return Ok(Some(Raw::String(source.string(max_len)?)));
+        }
+        b'-' | b'0'..=b'9' => {
+            machine.after_value();
+            return Ok(Some(Raw::Number(source.number()?)));
+        }
+        b't' => source.keyword(b"true").map(|()| Token::Bool(true))?,
+        b'f' => source.keyword(b"false").map(|()| Token::Bool(false))?,
+        b'n' => source.keyword(b"null").map(|()| Token::Null)?,
+        _ => return Err(source.locate(ErrorCode::ExpectedValue.into())),
+    };
+    machine.after_value();
+    Ok(Some(Raw::Token(raw)))
+}
+
+// Read the rest of a string, after the opening quote, up to and including the
+// closing quote. Escapes are checked but kept as they are, every byte of the
+// text goes through `take`.
+fn scan_string<S: Source>(source: &mut S, max_len: usize) -> Result<()> {
+    let mut len = 0;
+    loop {
+        match source.peek()? {
+            Some(b'"') => {
+                source.discard();
+                return Ok(());
+            }
+            Some(b'\\') => {
+                source.take();
+                len += 1 + scan_escape(source)?;
+            }
+            Some(0x00..=0x1F) => {
+                return Err(source.locate(ErrorCode::ControlCharacterInString.into()));
+            }
+            Some(_) => {
+                source.take();
+                len += 1;
+            }
+            None => return Err(source.locate(ErrorCode::Eof.into())),
+        }
+        // Checked as the string is read, so that a huge string is not read in
+        // full only to be thrown away.
+        if len > max_len {
+            return Err(source.locate(ErrorCode::StringTooLong.into()));
+        }
+    }
+}
+
+// Check the escape after a backslash, returning its length. A `\u` escape of
+// a UTF-16 surrogate has to be half of a pair, high then low, as it stands for
+// no character on its own.
+fn scan_escape<S: Source>(source: &mut S) -> Result<usize> {
+    match source.peek()? {
+        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {
+            source.take();
+            Ok(1)
+        }
+        Some(b'u') => {
+            source.take();
+            match scan_hex(source)? {
+                0xD800..=0xDBFF => {
+                    for expected in [b'\\', b'u'] {
+                        if source.peek()? != Some(expected) {
+                            return Err(source.locate(ErrorCode::InvalidUnicodeCodePoint.into()));
+                        }
+                        source.take();
+                    }
+                    if !(0xDC00..=0xDFFF).contains(&scan_hex(source)?) {
+                        return Err(source.locate(ErrorCode::InvalidUnicodeCodePoint.into()));
+                    }
+                    Ok(11)
+                }
+                0xDC00..=0xDFFF => Err(source.locate(ErrorCode::InvalidUnicodeCodePoint.into())),
+                _ => Ok(5),
+            }
+        }
+        Some(_) => Err(source.locate(ErrorCode::InvalidEscape.into())),
+        None => Err(source.locate(ErrorCode::Eof.into())),
+    }
+}
+
+// The four hex digits of a `\u` escape.
+fn scan_hex<S: Source>(source: &mut S) -> Result<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let Some(b) = source.peek()? else {
+            return Err(source.locate(ErrorCode::Eof.into()));
+        };
+        let Some(digit) = (b as char).to_digit(16) else {
+            return Err(source.locate(ErrorCode::InvalidEscape.into()));
+        };
+        source.take();
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
+
+// Check that `source` holds nothing after the value. Anything else, including
+// a value that has not been read to the end, is trailing input.
+fn end<S: Source>(source: &mut S, machine: &Machine) -> Result<()> {
+    source.skip_whitespace()?;
+    match (machine.state, source.peek()?) {
+        (State::Done, None) => Ok(()),
+        (_, None) => Err(source.locate(ErrorCode::Eof.into())),
+        (_, Some(_)) => Err(source.locate(ErrorCode::TrailingCharacters.into())),
+    }
+}
+
+// Read a number, checking it against the JSON grammar as it goes:
+//
+//     -? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?
+//
+// The number ends at the first byte the grammar cannot take, which is left
+// for the tokenizer to deal with. Errors are not located, the sources point
+// them at the start of the number.
+fn scan_number<S: Source>(source: &mut S) -> Result<()> {
+    fn digits<S: Source>(source: &mut S) -> Result<usize> {
+        let mut count = 0;
+        while let Some(b'0'..=b'9') = source.peek()? {
+            source.take();
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // A number cut short by the end of the input is incomplete rather than
+    // malformed.
+    fn missing<S: Source>(source: &mut S) -> Result<()> {
+        match source.peek()? {
+            Some(_) => Err(ErrorCode::InvalidNumber.into()),
+            None => Err(ErrorCode::Eof.into()),
+        }
+    }
+
+    if source.peek()? == Some(b'-') {
+        source.take();
+    }
+    match source.peek()? {
+        Some(b'0') => {
+            source.take();
+            // Leading zeros are not allowed, `0` is only ever followed by a
+            // fraction, an exponent or the end of the number.
+            if let Some(b'0'..=b'9') = source.peek()? {
+                return Err(ErrorCode::InvalidNumber.into());
+            }
+        }
+        Some(b'1'..=b'9') => {
+            digits(source)?;
+        }
+        _ => return missing(source),
+    }
+    if source.peek()? == Some(b'.') {
+        source.take();
+        if digits(source)? == 0 {
+            return missing(source);
+        }
+    }
+    if let Some(b'e' | b'E') = source.peek()? {
+        source.take();
+        if let Some(b'+' | b'-') = source.peek()? {
+            source.take();
+        }
+        if digits(source)? == 0 {
+            return missing(source);
+        }
+    }
+    Ok(())
+}
+
+struct StrSource<'de> {
+    input: &'de str,
+    // How far into the input the tokenizer is.
+    pos: usize,
+}
+
+impl<'de> StrSource<'de> {
+    fn rest(&self) -> &'de str {
+        &self.input[self.pos..]
+    }
+}
+
+impl<'de> Source for StrSource<'de> {
+    type Text<'s>
+        = &'de str
+    where
+        Self: 's;
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.as_bytes().get(self.pos).copied())
+    }
+
+    fn discard(&mut self) {
+        self.pos += 1;
+    }
+
+    fn offset(&self) -> usize {
+        self.pos
+    }
+
+    fn locate(&self, err: Error) -> Error {
+        err.locate(Position::in_str(self.input, self.pos), self.rest())
+    }
+
+    // The text is everything up to the closing quote. Quotes are ASCII, so
+    // they never turn up in the middle of a multi-byte character.
+    fn string(&mut self, max_len: usize) -> Result<&'de str> {
+        let start = self.pos;
+        scan_string(self, max_len)?;
+        Ok(&self.input[start..self.pos - 1])
+    }
+
+    fn number(&mut self) -> Result<&'de str> {
+        let start = self.pos;
+        if let Err(err) = scan_number(self) {
+            self.pos = start;
+            return Err(self.locate(err));
+        }
+        Ok(&self.input[start..self.pos])
+    }
+}
+
+struct IoSource<R> {
+    reader: BufReader<R>,
+    // The text of the last string or number.
+    scratch: Vec<u8>,
+    // Counted as the bytes go by, there is no input to work it out from.
+    position: Position,
+}
+
+impl<R: io::Read> IoSource<R> {
+    fn text(&self) -> Result<&str> {
+        std::str::from_utf8(&self.scratch).map_err(|_| self.locate(ErrorCode::InvalidUtf8.into()))
+    }
+}
+
+impl<R: io::Read> Source for IoSource<R> {
+    type Text<'s>
+        = &'s str
+    where
+        Self: 's;
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn discard(&mut self) {
+        let b = self.reader.buffer()[0];
+        self.reader.consume(1);
+        self.position.offset += 1;
+        if b == b'\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else if b & 0xC0 != 0x80 {
+            // Continuation bytes belong to the character before them.
+            self.position.column += 1;
+        }
+    }
+
+    fn take(&mut self) {
+        self.scratch.push(self.reader.buffer()[0]);
+        self.discard();
+    }
+
+    fn offset(&self) -> usize {
+        self.position.offset
+    }
+
+    // The snippet comes from what is left in the buffer, which can end in the
+    // middle of a character.
+    fn locate(&self, err: Error) -> Error {
+        let buffer = self.reader.buffer();
+        let rest = String::from_utf8_lossy(&buffer[..buffer.len().min(128)]);
+        err.locate(self.position, &rest)
+    }
+
+    fn string(&mut self, max_len: usize) -> Result<&str> {
+        self.scratch.clear();
+        scan_string(self, max_len)?;
+        self.text()
+    }
+
+    // The input cannot be rewound, so errors are pointed at the start of the
+    // number by hand, with what was read of it put back in front of the
+    // snippet.
+    fn number(&mut self) -> Result<&str> {
+        let start = self.position;
+        self.scratch.clear();
+        if let Err(err) = scan_number(self) {
+            let buffer = self.reader.buffer();
+            let mut rest = self.scratch.clone();
+            rest.extend_from_slice(&buffer[..buffer.len().min(128)]);
+            return Err(err.locate(start, &String::from_utf8_lossy(&rest)));
+        }
+        self.text()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::desert::Limits;
+    use crate::error::{Error, ErrorCode};
+    use crate::token::{ReaderTokenizer, Token, Tokenizer, unescape};
+    use std::borrow::Cow;
+    use std::io;
+
+    fn tokens(input: &str) -> Result<Vec<Token<'_>>, Error> {
+        let mut tokenizer = Tokenizer::new(input);
+        let mut tokens = Vec::new();
+        while let Some(token) = tokenizer.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens(r#" {"a": [1, -2.5e3, "x\"y"], "b": {}, "c": [true, false, null]} "#).unwrap(),
+            vec![
+                Token::StartObject,
+                Token::Key("a"),
+                Token::StartArray,
+                Token::Number("1"),
+                Token::Number("-2.5e3"),
+                Token::String(r#"x\"y"#),
+                Token::EndArray,
+                Token::Key("b"),
+                Token::StartObject,
+                Token::EndObject,
+                Token::Key("c"),
+                Token::StartArray,
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::Null,
+                Token::EndArray,
+                Token::EndObject,
+            ]
+        );
+        assert_eq!(
+            tokens("[]").unwrap(),
+            vec![Token::StartArray, Token::EndArray]
+        );
+        assert_eq!(tokens("0").unwrap(), vec![Token::Number("0")]);
+
+        assert!(matches!(
+            tokens("[1 2]").unwrap_err().code(),
+            ErrorCode::ExpectedArrayComma
+        ));
+        assert!(matches!(
+            tokens("[1,]").unwrap_err().code(),
+            ErrorCode::ExpectedValue
+        ));
+        assert!(matches!(
+            tokens(r#"{"a" 1}"#).unwrap_err().code(),
+            ErrorCode::ExpectedMapColon
+        ));
+        assert!(matches!(
+            tokens(r#"{"a":1 "b":2}"#).unwrap_err().code(),
+            ErrorCode::ExpectedMapComma
+        ));
+        assert!(matches!(
+            tokens("{1:2}").unwrap_err().code(),
+            ErrorCode::ExpectedString
+        ));
+        assert!(matches!(
+            tokens("[1}").unwrap_err().code(),
+            ErrorCode::ExpectedArrayComma
+        ));
+        assert!(matches!(
+            tokens("[tru]").unwrap_err().code(),
+            ErrorCode::ExpectedValue
+        ));
+        assert!(matches!(
+            tokens("[01]").unwrap_err().code(),
+            ErrorCode::InvalidNumber
+        ));
+        assert!(matches!(tokens("[1").unwrap_err().code(), ErrorCode::Eof));
+        assert!(matches!(tokens("1e").unwrap_err().code(), ErrorCode::Eof));
+        assert!(matches!(
+            tokens(r#""abc"#).unwrap_err().code(),
+            ErrorCode::Eof
+        ));
+        assert!(matches!(tokens("").unwrap_err().code(), ErrorCode::Eof));
+
+        assert_eq!(
+            tokens(r#""\ud83d\ude00\/""#).unwrap(),
+            vec![Token::String(r#"\ud83d\ude00\/"#)]
+        );
+        assert!(matches!(
+            tokens(r#""\x""#).unwrap_err().code(),
+            ErrorCode::InvalidEscape
+        ));
+        assert!(matches!(
+            tokens(r#""\u12""#).unwrap_err().code(),
+            ErrorCode::InvalidEscape
+        ));
+        assert!(matches!(
+            tokens(r#""\ud800""#).unwrap_err().code(),
+            ErrorCode::InvalidUnicodeCodePoint
+        ));
+        assert!(matches!(
+            tokens(r#""\udc00\ud800""#).unwrap_err().code(),
+            ErrorCode::InvalidUnicodeCodePoint
+        ));
+        assert!(matches!(
+            tokens("\"a\nb\"").unwrap_err().code(),
+            ErrorCode::ControlCharacterInString
+        ));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert!(matches!(unescape("plain"), Cow::Borrowed("plain")));
+        assert_eq!(
+            unescape(r#"a\"b\\c\/\b\f\n\r\t"#),
+            "a\"b\\c/\u{8}\u{c}\n\r\t"
+        );
+        assert_eq!(unescape(r#"\u00e9\u20AC"#), "é€");
+        assert_eq!(unescape(r#"\ud83d\ude00!"#), "😀!");
+        // Not from the tokenizer, so not checked.
+        assert_eq!(unescape(r#"\ud83d\u0041"#), "\u{fffd}A");
+        assert_eq!(unescape(r#"\udc00\x\u12"#), "\u{fffd}\u{fffd}\u{fffd}12");
+        assert_eq!(unescape("\\"), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_tokenizer_limits() {
+        let limits = Limits {
+            max_depth: 2,
+            max_string_len: 3,
+            max_collection_len: 2,
+        };
+        let tokens = |input| -> Result<usize, Error> {
+            let mut tokenizer = Tokenizer::with_limits(input, limits);
+            let mut count = 0;
+            while tokenizer.next_token()?.is_some() {
+                count += 1;
+            }
+            Ok(count)
+        };
+        assert_eq!(tokens(r#"[{"abc":1},[]]"#).unwrap(), 8);
+        assert!(matches!(
+            tokens("[[[]]]").unwrap_err().code(),
+            ErrorCode::RecursionLimitExceeded
+        ));
+        assert!(matches!(
+            tokens(r#"{"abcd":1}"#).unwrap_err().code(),
+            ErrorCode::StringTooLong
+        ));
+        assert!(matches!(
+            tokens("[1,2,3]").unwrap_err().code(),
+            ErrorCode::CollectionTooLarge
+        ));
+        assert!(matches!(
+            tokens(r#"{"a":1,"b":2,"c":3}"#).unwrap_err().code(),
+            ErrorCode::CollectionTooLarge
+        ));
+    }
+
+    #[test]
+    fn test_skip_value() {
+        let input =
+            r#"{"users": [{"name": "a", "tags": [[], {"x": 1}]}, {"name": "b"}], "count": 2}"#;
+        let mut tokenizer = Tokenizer::new(input);
+        assert_eq!(tokenizer.next_token().unwrap(), Some(Token::StartObject));
+        assert_eq!(tokenizer.next_token().unwrap(), Some(Token::Key("users")));
+        tokenizer.skip_value().unwrap();
+        assert_eq!(tokenizer.depth(), 1);
+        assert_eq!(tokenizer.next_token().unwrap(), Some(Token::Key("count")));
+        assert_eq!(tokenizer.next_token().unwrap(), Some(Token::Number("2")));
+        assert!(matches!(
+            tokenizer.skip_value().unwrap_err().code(),
+            ErrorCode::ExpectedValue
+        ));
+
+        let mut tokenizer = Tokenizer::new("[1, [2]]");
+        tokenizer.next_token().unwrap();
+        tokenizer.skip_value().unwrap();
+        tokenizer.skip_value().unwrap();
+        assert_eq!(tokenizer.next_token().unwrap(), Some(Token::EndArray));
+        assert_eq!(tokenizer.next_token().unwrap(), None);
+        tokenizer.end().unwrap();
+
+        let mut tokenizer = Tokenizer::new("[] []");
+        tokenizer.skip_value().unwrap();
+        assert!(matches!(
+            tokenizer.end().unwrap_err().code(),
+            ErrorCode::TrailingCharacters
+        ));
+    }
+
+    // Hands out the input a few bytes at a time, so tokens arrive in pieces.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_reader_tokenizer() {
+        let input = r#"{"name": "über", "ids": [12345, -0.5e-3], "ok": true, "skip": {"a": [1]}}"#;
+        let mut tokenizer = ReaderTokenizer::new(Trickle(input.as_bytes()));
+        let mut tokens = Vec::new();
+        while let Some(token) = tokenizer.next_token().unwrap() {
+            if token == Token::Key("skip") {
+                tokenizer.skip_value().unwrap();
+                continue;
+            }
+            tokens.push(format!("{token:?}"));
+        }
+        assert_eq!(
+            tokens,
+            [
+                "StartObject",
+                r#"Key("name")"#,
+                r#"String("über")"#,
+                r#"Key("ids")"#,
+                "StartArray",
+                r#"Number("12345")"#,
+                r#"Number("-0.5e-3")"#,
+                "EndArray",
+                r#"Key("ok")"#,
+                "Bool(true)",
+                "EndObject",
+            ]
+        );
+
+        let read = |input: &'static [u8]| -> Result<(), Error> {
+            let mut tokenizer = ReaderTokenizer::new(input);
+            while tokenizer.next_token()?.is_some() {}
+            Ok(())
+        };
+        let error = |input| read(input).unwrap_err();
+        assert!(read(b"[1, 2]").is_ok());
+        assert!(matches!(
+            error(b"[1-2]").code(),
+            ErrorCode::ExpectedArrayComma
+        ));
+        assert!(matches!(error(b"1e").code(), ErrorCode::Eof));
+        assert!(matches!(error(b"[1e]").code(), ErrorCode::InvalidNumber));
+        assert!(matches!(
+            error(b"[\"\xff\"]").code(),
+            ErrorCode::InvalidUtf8
+        ));
+        assert!(matches!(error(b"{\"a\":").code(), ErrorCode::Eof));
+
+        // Positions are counted in characters as the input goes by.
+        let err = error("[1,\n \"ü\", ]".as_bytes());
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.column(), Some(7));
+        assert_eq!(err.snippet(), "]");
+
+        // Number errors point at the start of the number.
+        let err = error(b"[1, -01]");
+        assert_eq!(err.column(), Some(5));
+        assert_eq!(err.snippet(), "-01]");
+    }
+
+    #[test]
+    fn test_sources_agree() {
+        // Both tokenizers read the same grammar, so they fail with the same
+        // code at the same place.
+        let str_error = |input| {
+            let mut tokenizer = Tokenizer::new(input);
+            loop {
+                match tokenizer.next_token() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => return None,
+                    Err(err) => return Some(err),
+                }
+            }
+        };
+        let reader_error = |input: &str| {
+            let mut tokenizer = ReaderTokenizer::new(Trickle(input.as_bytes()));
+            loop {
+                match tokenizer.next_token() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => return None,
+                    Err(err) => return Some(err),
+                }
+            }
+        };
+        for input in [
+            r#"["a\"b", "\\", "\/\b\f\n\r\t", "\u00e9", "\ud83d\ude00"]"#,
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\u12g4""#,
+            r#""\ud800""#,
+            r#""\ud800\u0041""#,
+            r#""\udc00""#,
+            "\"a\u{1}b\"",
+            "\"a\tb\"",
+            r#""\"#,
+            "[1, -0.5e+3, 0, 10E2]",
+            "[1-2]",
+            "[1.2.3]",
+            "[01]",
+            "[-]",
+            "[1.]",
+            "[1e]",
+            "1e",
+            "-",
+            "[1x]",
+        ] {
+            let expected = str_error(input);
+            let actual = reader_error(input);
+            assert_eq!(
+                expected.as_ref().map(|err| format!("{:?}", err.code())),
+                actual.as_ref().map(|err| format!("{:?}", err.code())),
+                "{input}"
+            );
+            assert_eq!(
+                expected.and_then(|err| err.position()),
+                actual.and_then(|err| err.position()),
+                "{input}"
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// JSON has no byte type, so byte strings (`serialize_bytes`, and fields using
+// `serde_bytes`) need an encoding. Serializer and deserializer have to agree
+// on it, both take it as an option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BytesEncoding {
+    // An array of numbers, `[104,105]`. Any JSON parser can read it, but it
+    // takes up to four bytes of text per byte.
+    #[default]
+    Array,
+    // A string in standard, padded base64 (RFC 4648), `"aGk="`. About a third
+    // larger than the bytes themselves, which suits hashes and images.
+    Base64,
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Every three bytes become four characters, a shorter last group is padded
+// with `=`.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let byte = |i: usize| chunk.get(i).copied().unwrap_or(0);
+        let bits = u32::from_be_bytes([0, byte(0), byte(1), byte(2)]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// The inverse of `encode_base64`. Padding is required and nothing else may
+// appear in the text, not even whitespace. `None` if the text is not base64.
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    fn value(ch: u8) -> Option<u32> {
+        ALPHABET.iter().position(|&c| c == ch).map(|i| i as u32)
+    }
+
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for (i, group) in text.chunks(4).enumerate() {
+        let last = i == text.len() / 4 - 1;
+        let padding = group.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut bits = 0;
+        for &ch in &group[..4 - padding] {
+            bits = bits << 6 | value(ch)?;
+        }
+        bits <<= 6 * padding;
+        out.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+#[test]
+fn test_base64() {
+    for (bytes, text) in [
+        (&b""[..], ""),
+        (b"f", "Zg=="),
+        (b"fo", "Zm8="),
+        (b"foo", "Zm9v"),
+        (b"foob", "Zm9vYg=="),
+        (b"fooba", "Zm9vYmE="),
+        (b"foobar", "Zm9vYmFy"),
+        (&[0xFF, 0xFE, 0x00], "//4A"),
+    ] {
+        assert_eq!(encode_base64(bytes), text);
+        assert_eq!(decode_base64(text).as_deref(), Some(bytes));
+    }
+    for invalid in ["Zg", "Zg=", "Z===", "Zg==Zg==", "Zm9v YmFy", "Zm9-", "===="] {
+        assert_eq!(decode_base64(invalid), None, "{invalid}");
+    }
+}
@@ -1,14 +1,77 @@
-use crate::{Error, Result};
+use crate::bytes::{self, BytesEncoding};
+use crate::error::{Error, ErrorCode, Result};
 use serde::Deserialize;
-use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::any::type_name;
+use std::borrow::Cow;
+use std::io;
+use std::str::FromStr;
+
+mod options;
+mod stream;
+
+pub use options::{DeserializerOptions, Limits};
+pub use stream::{ReaderStreamDeserializer, StreamDeserializer};
+
 pub struct Deserializer<'de> {
+    // The whole document, kept around to report error positions.
+    original: &'de str,
+    // What is left to parse.
     input: &'de str,
+    options: DeserializerOptions,
+    // How many more arrays and objects may be opened at the current depth.
+    remaining_depth: usize,
 }
 
 impl<'de> Deserializer<'de> {
     // By convention, `Deserializer` constructors are named like `from_xyz`.
-    pub fn from_str<'a>(input: &'de str) -> Self {
-        Deserializer { input }
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Self {
+        Deserializer::with_options(input, DeserializerOptions::new())
+    }
+
+    pub fn with_limits(input: &'de str, limits: Limits) -> Self {
+        Deserializer::with_options(input, DeserializerOptions::new().limits(limits))
+    }
+
+    pub fn with_options(input: &'de str, options: DeserializerOptions) -> Self {
+        Deserializer {
+            original: input,
+            input,
+            options,
+            remaining_depth: options.limits.max_depth,
+        }
+    }
+
+    // Byte offset of the next character to be parsed.
+    fn offset(&self) -> usize {
+        self.original.len() - self.input.len()
+    }
+
+    // An error pointing at the next character to be parsed. Parsing functions
+    // peek before they consume so that this is the offending character.
+    fn error(&self, code: ErrorCode) -> Error {
+        Error::at(code, self.original, self.offset())
+    }
+
+    // An error pointing `len` bytes past the next character, for tokens that
+    // are checked as a whole before being consumed.
+    fn error_ahead(&self, code: ErrorCode, len: usize) -> Error {
+        Error::at(code, self.original, self.offset() + len)
+    }
+
+    // Errors raised through `de::Error::custom` by `Deserialize` impls, like a
+    // missing field, know nothing about the input. Point them at the place
+    // parsing had reached.
+    fn fix_position(&self, mut err: Error) -> Error {
+        if !err.has_position() {
+            err.set_location(self.original, self.offset());
+        }
+        err
     }
 
     // SERDE IS NOT A PARSING LIBRARY. This impl block defines a few basic parsing
@@ -17,7 +80,10 @@ impl<'de> Deserializer<'de> {
 
     // Look at the first character in the input without consuming it.
     fn peek_char(&mut self) -> Result<char> {
-        self.input.chars().next().ok_or(Error::Eof)
+        match self.input.chars().next() {
+            Some(ch) => Ok(ch),
+            None => Err(self.error(ErrorCode::Eof)),
+        }
     }
 
     // Consume the first character in the input.
@@ -27,224 +93,666 @@ impl<'de> Deserializer<'de> {
         Ok(ch)
     }
 
-    // Parse a string until the next '"' character.
+    // JSON allows insignificant whitespace before and after every structural
+    // character and value, but never inside a token. Comments, if enabled, are
+    // skipped the same way. An unterminated block comment runs to the end of
+    // the input, so whatever comes next reports the end of input.
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.input = self.input.trim_start_matches([' ', '\t', '\n', '\r']);
+            if !self.options.comments {
+                return;
+            }
+            if let Some(rest) = self.input.strip_prefix("//") {
+                self.input = rest.find('\n').map_or("", |i| &rest[i..]);
+            } else if let Some(rest) = self.input.strip_prefix("/*") {
+                self.input = rest.find("*/").map_or("", |i| &rest[i + 2..]);
+            } else {
+                return;
+            }
+        }
+    }
+
+    // Whether `ch` opens a string, which may also be a single quote.
+    fn is_quote(&self, ch: char) -> bool {
+        ch == '"' || (ch == '\'' && self.options.single_quotes)
+    }
+
+    // Like `peek_char` but skipping over any whitespace first.
+    fn peek_token(&mut self) -> Result<char> {
+        self.skip_whitespace();
+        self.peek_char()
+    }
+
+    // Like `consume_keyword` but skipping over any whitespace first.
+    fn consume_token(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        self.consume_keyword(keyword)
+    }
+
+    // Consume the next non-whitespace character if it is `expected`.
+    fn expect(&mut self, expected: char, code: ErrorCode) -> Result<()> {
+        if self.peek_token()? == expected {
+            self.next_char()?;
+            Ok(())
+        } else {
+            Err(self.error(code))
+        }
+    }
+
+    // Consume the given keyword if the input starts with it.
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if self.input.starts_with(keyword) {
+            self.input = &self.input[keyword.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    // Called on entering an array or object, with `leave` called once it is
+    // done. Errors on entering one level deeper than the limit allows.
+    fn enter(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        if self.remaining_depth == 0 {
+            return Err(self.error(ErrorCode::RecursionLimitExceeded));
+        }
+        self.remaining_depth -= 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.remaining_depth += 1;
+    }
+
+    // Parse the JSON identifier `true` or `false`.
+    fn parse_bool(&mut self) -> Result<bool> {
+        if self.consume_token("true") {
+            Ok(true)
+        } else if self.consume_keyword("false") {
+            Ok(false)
+        } else {
+            Err(self.error(ErrorCode::ExpectedBoolean))
+        }
+    }
+
+    // Find the number at the start of the input and check it against the
+    // JSON grammar:
     //
-    // Makes no attempt to handle escape sequences. What did you expect? This is
-    // example code!
-    fn parse_string(&mut self) -> Result<&'de str> {
-        if self.next_char()? != '"' {
-            return Err(Error::ExpectedString);
-        }
-        match self.input.find('"') {
-            Some(len) => {
-                let s = &self.input[..len];
-                self.input = &self.input[len + 1..];
-                Ok(s)
+    //     -? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?
+    //
+    // Returns the text of the number and whether it is an integer, that is
+    // whether it has neither a fraction nor an exponent. Nothing is consumed.
+    // `code` is the error for input that does not start like a number at all.
+    fn scan_number(&mut self, code: ErrorCode) -> Result<(&'de str, bool)> {
+        fn digits(bytes: &[u8]) -> usize {
+            bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+        }
+
+        self.peek_token()?;
+        let bytes = self.input.as_bytes();
+        // A number cut short by the end of the input is incomplete rather
+        // than malformed.
+        let invalid = |len| {
+            if len == bytes.len() {
+                ErrorCode::Eof
+            } else {
+                ErrorCode::InvalidNumber
+            }
+        };
+        let mut len = 0;
+        if bytes.first() == Some(&b'-') {
+            len += 1;
+        }
+        match bytes.get(len) {
+            Some(b'0') => len += 1,
+            Some(b'1'..=b'9') => len += digits(&bytes[len..]),
+            _ if len == 0 => return Err(self.error(code)),
+            _ => return Err(self.error_ahead(invalid(len), len)),
+        }
+        let mut integer = true;
+        if bytes.get(len) == Some(&b'.') {
+            integer = false;
+            len += 1;
+            match digits(&bytes[len..]) {
+                0 => return Err(self.error_ahead(invalid(len), len)),
+                n => len += n,
+            }
+        }
+        if matches!(bytes.get(len), Some(b'e' | b'E')) {
+            integer = false;
+            len += 1;
+            if matches!(bytes.get(len), Some(b'+' | b'-')) {
+                len += 1;
             }
-            None => Err(Error::Eof),
+            match digits(&bytes[len..]) {
+                0 => return Err(self.error_ahead(invalid(len), len)),
+                n => len += n,
+            }
+        }
+        // Leading zeros are not allowed, `0` is only ever followed by a
+        // fraction, an exponent or the end of the number.
+        if bytes.get(len).is_some_and(u8::is_ascii_digit) {
+            return Err(self.error_ahead(ErrorCode::InvalidNumber, len));
         }
+        Ok((&self.input[..len], integer))
+    }
+
+    // Parse a JSON integer as any of the primitive integer types. The digits
+    // are converted by the standard library, which checks for overflow, so a
+    // number that does not fit in T is reported as out of range instead of
+    // wrapping around. That includes negative numbers for unsigned types.
+    fn parse_integer<T: FromStr>(&mut self) -> Result<T> {
+        let (number, integer) = self.scan_number(ErrorCode::ExpectedInteger)?;
+        if !integer {
+            // Point at the fraction or exponent that makes it a float.
+            let len = number.find(['.', 'e', 'E']).unwrap_or(0);
+            return Err(self.error_ahead(ErrorCode::ExpectedInteger, len));
+        }
+        let int = number
+            .parse()
+            .map_err(|_| self.error(ErrorCode::NumberOutOfRange(type_name::<T>())))?;
+        self.input = &self.input[number.len()..];
+        Ok(int)
+    }
+
+    // Parse a JSON number as `f32` or `f64`. The standard library rounds the
+    // decimal text correctly for T, only numbers too large for T, which it
+    // turns into infinity, need to be rejected.
+    //
+    // With the option enabled, `NaN`, `Infinity` and `-Infinity` are accepted
+    // as well. The standard library parses these spellings too.
+    fn parse_float<T: FromStr + Into<f64> + Copy>(&mut self) -> Result<T> {
+        if let Some(keyword) = self.non_finite_keyword() {
+            let float = keyword
+                .parse()
+                .map_err(|_| self.error(ErrorCode::ExpectedFloat))?;
+            self.input = &self.input[keyword.len()..];
+            return Ok(float);
+        }
+        let (number, _) = self.scan_number(ErrorCode::ExpectedFloat)?;
+        let float: T = number
+            .parse()
+            .map_err(|_| self.error(ErrorCode::ExpectedFloat))?;
+        if float.into().is_infinite() {
+            return Err(self.error(ErrorCode::NumberOutOfRange(type_name::<T>())));
+        }
+        self.input = &self.input[number.len()..];
+        Ok(float)
+    }
+
+    // The non-finite literal at the start of the input, if they are enabled.
+    fn non_finite_keyword(&mut self) -> Option<&'static str> {
+        if !self.options.nan_and_infinity {
+            return None;
+        }
+        self.skip_whitespace();
+        ["NaN", "Infinity", "-Infinity"]
+            .into_iter()
+            .find(|keyword| self.input.starts_with(keyword))
+    }
+
+    // Hand a number to a visitor that accepts any type. Integers go to
+    // `visit_u64` or `visit_i64` so they stay exact. Everything else, and
+    // integers too large for 64 bits, goes to `visit_f64`.
+    fn deserialize_number<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.non_finite_keyword().is_some() {
+            return visitor.visit_f64(self.parse_float()?);
+        }
+        let (number, integer) = self.scan_number(ErrorCode::Syntax)?;
+        if integer {
+            if let Ok(int) = number.parse() {
+                self.input = &self.input[number.len()..];
+                return visitor.visit_u64(int);
+            }
+            if let Ok(int) = number.parse() {
+                self.input = &self.input[number.len()..];
+                return visitor.visit_i64(int);
+            }
+        }
+        visitor.visit_f64(self.parse_float()?)
+    }
+
+    // Parse a JSON string, decoding escape sequences.
+    //
+    // Strings without escapes are the common case and are handed out as a
+    // slice of the input. Only once a backslash shows up do we have to allocate
+    // a `String` to hold the decoded text.
+    //
+    // Strings over the length limit are reported at their opening quote.
+    fn parse_string(&mut self) -> Result<Cow<'de, str>> {
+        let quote = self.peek_token()?;
+        if !self.is_quote(quote) {
+            return Err(self.error(ErrorCode::ExpectedString));
+        }
+        let start = self.offset();
+        self.next_char()?;
+        let too_long = |de: &Self| Error::at(ErrorCode::StringTooLong, de.original, start);
+        let bytes = self.input.as_bytes();
+        let mut len = 0;
+        loop {
+            match bytes.get(len) {
+                Some(&b) if b == quote as u8 && len > self.options.limits.max_string_len => {
+                    return Err(too_long(self));
+                }
+                Some(&b) if b == quote as u8 => {
+                    let s = &self.input[..len];
+                    self.input = &self.input[len + 1..];
+                    return Ok(Cow::Borrowed(s));
+                }
+                Some(b'\\') => break,
+                Some(0x00..=0x1F) => {
+                    self.input = &self.input[len..];
+                    return Err(self.error(ErrorCode::ControlCharacterInString));
+                }
+                Some(_) => len += 1,
+                None => {
+                    self.input = &self.input[len..];
+                    return Err(self.error(ErrorCode::Eof));
+                }
+            }
+        }
+        let mut string = self.input[..len].to_string();
+        self.input = &self.input[len..];
+        loop {
+            if self.peek_char()? < '\u{20}' {
+                return Err(self.error(ErrorCode::ControlCharacterInString));
+            }
+            match self.next_char()? {
+                ch if ch == quote => return Ok(Cow::Owned(string)),
+                '\\' => string.push(self.parse_escape()?),
+                ch => string.push(ch),
+            }
+            // Checked as the string grows, so that a huge string is not
+            // decoded in full only to be thrown away.
+            if string.len() > self.options.limits.max_string_len {
+                return Err(too_long(self));
+            }
+        }
+    }
+
+    // Decode the escape sequence following a backslash.
+    fn parse_escape(&mut self) -> Result<char> {
+        let ch = match self.peek_char()? {
+            '"' => '"',
+            '\'' if self.options.single_quotes => '\'',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                self.next_char()?;
+                return self.parse_unicode_escape();
+            }
+            _ => return Err(self.error(ErrorCode::InvalidEscape)),
+        };
+        self.next_char()?;
+        Ok(ch)
+    }
+
+    // Decode a `\uXXXX` escape. Characters outside the Basic Multilingual Plane
+    // are written as a UTF-16 surrogate pair, `\uD83D\uDE00`, which has to be
+    // combined back into a single code point.
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let code = match self.parse_hex4()? {
+            high @ 0xD800..=0xDBFF => {
+                if !self.consume_keyword("\\u") {
+                    return Err(self.error(ErrorCode::InvalidUnicodeCodePoint));
+                }
+                let low = self.parse_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error(ErrorCode::InvalidUnicodeCodePoint));
+                }
+                0x10000 + (((high - 0xD800) << 10) | (low - 0xDC00))
+            }
+            code => code,
+        };
+        // Lone low surrogates are the only values `from_u32` still rejects.
+        char::from_u32(code).ok_or_else(|| self.error(ErrorCode::InvalidUnicodeCodePoint))
     }
-}
 
-// This basic deserializer supports only `from_str`.
-fn from_str<'a, T: Deserialize<'a>>(s: &'a str) -> Result<T> {
-    let mut deserializer = Deserializer::from_str(s);
-    let t = T::deserialize(&mut deserializer)?;
-    if deserializer.input.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingCharacters)
+    // Parse exactly four hex digits.
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .peek_char()?
+                .to_digit(16)
+                .ok_or_else(|| self.error(ErrorCode::InvalidEscape))?;
+            self.next_char()?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+// Deserialize strict JSON, see `DeserializerOptions` for the extensions.
+//
+// The result may borrow from `s`. A JSON string without escape sequences is
+// exactly the text between its quotes, so it is handed out as a slice of the
+// input and never copied:
+//
+// - `&'a str` fields point into `s`. A string containing escapes has no such
+//   slice and is an error for them.
+// - `&'a [u8]` fields are the UTF-8 bytes of such a string, with the same
+//   restriction.
+// - `Cow<'a, str>` and `Cow<'a, [u8]>` fields marked `#[serde(borrow)]` are
+//   `Cow::Borrowed` when the string has no escapes and fall back to
+//   `Cow::Owned` when it does. Without the attribute serde always makes them
+//   owned.
+// - Map keys and values follow the same rules. `#[serde(borrow)]` only reaches
+//   a `Cow` that is the field itself though, one nested in a collection is
+//   always owned.
+pub fn from_str<'a, T: Deserialize<'a>>(s: &'a str) -> Result<T> {
+    DeserializerOptions::new().from_str(s)
+}
+
+// Like `from_str` but with custom limits on nesting and sizes, for input that
+// comes from untrusted sources.
+pub fn from_str_with_limits<'a, T: Deserialize<'a>>(s: &'a str, limits: Limits) -> Result<T> {
+    DeserializerOptions::new().limits(limits).from_str(s)
+}
+
+pub fn from_slice<'a, T: Deserialize<'a>>(v: &'a [u8]) -> Result<T> {
+    DeserializerOptions::new().from_slice(v)
+}
+
+pub fn from_reader<R: io::Read, T: DeserializeOwned>(reader: R) -> Result<T> {
+    DeserializerOptions::new().from_reader(reader)
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // Look at the input data to decide what Serde data model type to
+    // deserialize as. Not all data formats are able to support this operation.
+    // Formats that support `deserialize_any` are known as self-describing.
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_token()? {
+            'n' => self.deserialize_unit(visitor),
+            't' | 'f' => self.deserialize_bool(visitor),
+            ch if self.is_quote(ch) => self.deserialize_str(visitor),
+            '0'..='9' | '-' => self.deserialize_number(visitor),
+            'N' | 'I' if self.options.nan_and_infinity => self.deserialize_number(visitor),
+            '[' => self.deserialize_seq(visitor),
+            '{' => self.deserialize_map(visitor),
+            _ => Err(self.error(ErrorCode::Syntax)),
+        }
+    }
+
+    // Uses the `parse_bool` parsing function defined above to read the JSON
+    // identifier `true` or `false` from the input.
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_bool(self.parse_bool()?)
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // The `parse_integer` function is generic over the integer type `T` so
+    // here it is invoked with `T=i8`. The next 9 methods are similar.
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_i8(self.parse_integer()?)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_i16(self.parse_integer()?)
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_i32(self.parse_integer()?)
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_i64(self.parse_integer()?)
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_i128(self.parse_integer()?)
     }
 
-    fn deserialize_u8<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_u8(self.parse_integer()?)
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_u16(self.parse_integer()?)
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_u32(self.parse_integer()?)
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_u64(self.parse_integer()?)
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_u128(self.parse_integer()?)
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // Floats are parsed straight into the requested type rather than going
+    // through `f64`, which could round twice.
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_f32(self.parse_float()?)
     }
 
-    fn deserialize_char<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_f64(self.parse_float()?)
+    }
+
+    // A `char` is a JSON string holding exactly one character.
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.skip_whitespace();
+        let offset = self.offset();
+        let s = self.parse_string()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => visitor.visit_char(ch),
+            _ => Err(Error::at(ErrorCode::ExpectedChar, self.original, offset)),
+        }
     }
 
     // Refer to the "Understanding deserializer lifetimes" page for information
     // about the three deserialization flavors of strings in Serde.
-    fn deserialize_str<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.parse_string()?)
+        match self.parse_string()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
     }
 
-    fn deserialize_string<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         self.deserialize_str(visitor)
     }
 
-    fn deserialize_bytes<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // JSON has no byte type. By default bytes are accepted either as a
+    // string, handed out as its UTF-8 encoding, or as an array of numbers.
+    // Like `deserialize_str` a string without escapes is borrowed from the
+    // input.
+    //
+    // With `BytesEncoding::Base64` they have to be a base64 string, which is
+    // always decoded into a new buffer.
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        if self.options.bytes == BytesEncoding::Base64 {
+            self.skip_whitespace();
+            let offset = self.offset();
+            let text = self.parse_string()?;
+            return match bytes::decode_base64(&text) {
+                Some(bytes) => visitor.visit_byte_buf(bytes),
+                None => Err(Error::at(ErrorCode::InvalidBase64, self.original, offset)),
+            };
+        }
+        match self.peek_token()? {
+            ch if self.is_quote(ch) => match self.parse_string()? {
+                Cow::Borrowed(s) => visitor.visit_borrowed_bytes(s.as_bytes()),
+                Cow::Owned(s) => visitor.visit_byte_buf(s.into_bytes()),
+            },
+            '[' => self.deserialize_seq(visitor),
+            _ => Err(self.error(ErrorCode::ExpectedBytes)),
+        }
     }
 
-    fn deserialize_byte_buf<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // An absent optional is represented as the JSON `null` and a present
+    // optional is represented as just the contained value.
+    //
+    // As commented in `Serializer` implementation, this is a lossy
+    // representation. For example the values `Some(())` and `None` both
+    // serialize as just `null`. Unfortunately this is typically what people
+    // expect when working with JSON. Other formats are encouraged to behave
+    // more intelligently if possible.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        if self.consume_token("null") {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
     }
 
-    fn deserialize_unit<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // In Serde, unit means an anonymous value containing no data.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        if self.consume_token("null") {
+            visitor.visit_unit()
+        } else {
+            Err(self.error(ErrorCode::ExpectedNull))
+        }
     }
 
-    fn deserialize_unit_struct<V>(
-        self,
-        name: &'static str,
-        visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
+    // Unit struct means a named value containing no data.
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(
-        self,
-        name: &'static str,
-        visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
+    // As is done here, serializers are encouraged to treat newtype structs as
+    // insignificant wrappers around the data they contain. That means not
+    // parsing anything other than the contained value.
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // Deserialization of compound types like sequences and maps happens by
+    // passing the visitor an "Access" object that gives it the ability to
+    // iterate through the data contained in the sequence.
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        // Parse the opening bracket of the sequence. A value of another type
+        // is reported as such even at the depth limit, and both errors point
+        // at the bracket, so it is only consumed once the depth is checked.
+        if self.peek_token()? != '[' {
+            return Err(self.error(ErrorCode::ExpectedArray));
+        }
+        self.enter()?;
+        self.next_char()?;
+        // Give the visitor access to each element of the sequence.
+        let value = visitor.visit_seq(CommaSeparated::new(self))?;
+        self.leave();
+        // Parse the closing bracket of the sequence.
+        self.expect(']', ErrorCode::ExpectedArrayEnd)?;
+        Ok(value)
     }
 
-    fn deserialize_tuple<V>(
-        self,
-        len: usize,
-        visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
+    // Tuples look just like sequences in JSON. Some formats may be able to
+    // represent tuples more efficiently.
+    //
+    // As indicated by the length parameter, the `Deserialize` implementation
+    // for a tuple in the Serde data model is required to know the length of the
+    // tuple before even looking at the input data.
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_seq(visitor)
     }
 
+    // Tuple structs look just like sequences in JSON.
     fn deserialize_tuple_struct<V>(
         self,
-        name: &'static str,
-        len: usize,
+        _name: &'static str,
+        _len: usize,
         visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
+    ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_seq(visitor)
     }
 
     // Much like `deserialize_seq` but calls the visitors `visit_map` method
@@ -254,19 +762,19 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        // Parse the opening brace of the map.
-        if self.next_char()? == '{' {
-            // Give the visitor access to each entry of the map.
-            let value = visitor.visit_map(CommaSeparated::new(self))?;
-            // Parse the closing brace of the map.
-            if self.next_char()? == '}' {
-                Ok(value)
-            } else {
-                Err(Error::ExpectedMapEnd)
-            }
-        } else {
-            Err(Error::ExpectedMap)
+        // Parse the opening brace of the map, checked the same way as the
+        // bracket of a sequence.
+        if self.peek_token()? != '{' {
+            return Err(self.error(ErrorCode::ExpectedMap));
         }
+        self.enter()?;
+        self.next_char()?;
+        // Give the visitor access to each entry of the map.
+        let value = visitor.visit_map(CommaSeparated::new(self))?;
+        self.leave();
+        // Parse the closing brace of the map.
+        self.expect('}', ErrorCode::ExpectedMapEnd)?;
+        Ok(value)
     }
 
     // Structs look just like maps in JSON.
@@ -287,51 +795,111 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_map(visitor)
     }
 
+    // A unit variant is a plain string, every other variant is a single-entry
+    // map keyed by the variant name: `{"Variant":...}`.
     fn deserialize_enum<V>(
         self,
-        name: &'static str,
-        variants: &'static [&'static str],
+        _name: &'static str,
+        _variants: &'static [&'static str],
         visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
+    ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        match self.peek_token()? {
+            // Visit a unit variant.
+            ch if self.is_quote(ch) => visitor.visit_enum(self.parse_string()?.into_deserializer()),
+            // Visit a newtype variant, tuple variant, or struct variant.
+            '{' => {
+                self.enter()?;
+                self.next_char()?;
+                let value = visitor.visit_enum(Enum::new(self))?;
+                self.leave();
+                // Parse the matching close brace.
+                self.expect('}', ErrorCode::ExpectedMapEnd)?;
+                Ok(value)
+            }
+            _ => Err(self.error(ErrorCode::ExpectedEnum)),
+        }
     }
 
     // An identifier in Serde is the type that identifies a field of a struct or
     // the variant of an enum. In JSON, struct fields and enum variants are
     // represented as strings. In other formats they may be represented as
     // numeric indices.
-    fn deserialize_identifier<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         self.deserialize_str(visitor)
     }
 
-    fn deserialize_ignored_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    // Like `deserialize_any` but indicates to the `Deserializer` that it makes
+    // no difference which `Visitor` method is called because the data is
+    // ignored.
+    //
+    // Some deserializers are able to implement this more efficiently than
+    // `deserialize_any`, for example by rapidly skipping over matched
+    // delimiters without paying close attention to the data in between.
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 }
 
 // In order to handle commas correctly when deserializing a JSON array or map,
 // we need to track whether we are on the first element or past the first
-// element.
+// element. Counting the elements also lets us enforce the size limit.
 struct CommaSeparated<'a, 'de> {
     de: &'a mut Deserializer<'de>,
-    first: bool,
+    len: usize,
 }
 
 impl<'a, 'de> CommaSeparated<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>) -> Self {
-        CommaSeparated { de, first: true }
+        CommaSeparated { de, len: 0 }
+    }
+
+    // Called before each element, after the comma that precedes it.
+    fn count(&mut self) -> Result<()> {
+        if self.len == self.de.options.limits.max_collection_len {
+            return Err(self.de.error(ErrorCode::CollectionTooLarge));
+        }
+        self.len += 1;
+        Ok(())
+    }
+}
+
+// `SeqAccess` is provided to the `Visitor` to give it the ability to iterate
+// through elements of the sequence.
+impl<'de, 'a> SeqAccess<'de> for CommaSeparated<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        // Check if there are no more elements.
+        if self.de.peek_token()? == ']' {
+            return Ok(None);
+        }
+        // Comma is required before every element except the first.
+        if self.len > 0 {
+            self.de.expect(',', ErrorCode::ExpectedArrayComma)?;
+            if self.de.options.trailing_commas && self.de.peek_token()? == ']' {
+                return Ok(None);
+            }
+        }
+        self.count()?;
+        // Deserialize an array element.
+        seed.deserialize(&mut *self.de).map(Some)
     }
 }
 
+// `MapAccess` is provided to the `Visitor` to give it the ability to iterate
+// through entries of the map.
 impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
     type Error = Error;
 
@@ -340,16 +908,19 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
         K: DeserializeSeed<'de>,
     {
         // Check if there are no more entries.
-        if self.de.peek_char()? == '}' {
+        if self.de.peek_token()? == '}' {
             return Ok(None);
         }
         // Comma is required before every entry except the first.
-        if !self.first && self.de.next_char()? != ',' {
-            return Err(Error::ExpectedMapComma);
+        if self.len > 0 {
+            self.de.expect(',', ErrorCode::ExpectedMapComma)?;
+            if self.de.options.trailing_commas && self.de.peek_token()? == '}' {
+                return Ok(None);
+            }
         }
-        self.first = false;
+        self.count()?;
         // Deserialize a map key.
-        seed.deserialize(&mut *self.de).map(Some)
+        seed.deserialize(MapKey { de: &mut *self.de }).map(Some)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
@@ -359,14 +930,215 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
         // It doesn't make a difference whether the colon is parsed at the end
         // of `next_key_seed` or at the beginning of `next_value_seed`. In this
         // case the code is a bit simpler having it here.
-        if self.de.next_char()? != ':' {
-            return Err(Error::ExpectedMapColon);
-        }
+        self.de.expect(':', ErrorCode::ExpectedMapColon)?;
         // Deserialize a map value.
         seed.deserialize(&mut *self.de)
     }
 }
 
+// Deserializes the keys of a map. Object keys are always strings, so a map
+// with integer or bool keys like `HashMap<u32, T>` reads them from inside the
+// quotes, `{"1":"a"}`. This mirrors what the serializer writes. Everything
+// else is read as a plain string.
+struct MapKey<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> MapKey<'_, 'de> {
+    // Parse the contents of a quoted key with `parse`, which returns the
+    // error code if they are not what it is looking for. Errors point at the
+    // opening quote of the key.
+    fn parse_quoted<T>(
+        &mut self,
+        parse: impl FnOnce(&str) -> std::result::Result<T, ErrorCode>,
+    ) -> Result<T> {
+        self.de.skip_whitespace();
+        let offset = self.de.offset();
+        let key = self.de.parse_string()?;
+        parse(&key).map_err(|code| Error::at(code, self.de.original, offset))
+    }
+
+    fn parse_integer<T: FromStr>(&mut self) -> Result<T> {
+        self.parse_quoted(parse_integer_key)
+    }
+}
+
+// Parse the contents of a quoted integer key. The same grammar as an integer
+// outside of quotes, so no `+`, leading zeros or whitespace, and every integer
+// has a single way to be written as a key. Also used for the keys of a
+// `Value`.
+pub(crate) fn parse_integer_key<T: FromStr>(key: &str) -> std::result::Result<T, ErrorCode> {
+    let digits = key.strip_prefix('-').unwrap_or(key);
+    let valid = !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    if !valid {
+        return Err(ErrorCode::ExpectedInteger);
+    }
+    key.parse()
+        .map_err(|_| ErrorCode::NumberOutOfRange(type_name::<T>()))
+}
+
+macro_rules! deserialize_integer_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(mut self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.parse_integer()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapKey<'_, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_str(self.de, visitor)
+    }
+
+    deserialize_integer_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+    }
+
+    fn deserialize_bool<V>(mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_bool(self.parse_quoted(|key| match key {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(ErrorCode::ExpectedBoolean),
+        })?)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_char(self.de, visitor)
+    }
+
+    // A key is never `null`, so an optional key is always there.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Only unit variants can be keys, which are plain strings.
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_enum(self.de, name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        f32 f64 str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct Enum<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> Enum<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        Enum { de }
+    }
+}
+
+// `EnumAccess` is provided to the `Visitor` to give it the ability to determine
+// which variant of the enum is supposed to be deserialized.
+//
+// Note that all enum deserialization methods in Serde refer exclusively to the
+// "externally tagged" enum representation.
+impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        // The `deserialize_enum` method parsed a `{` character so we are
+        // currently inside of a map. The seed will be deserializing itself from
+        // the key of the map.
+        let val = seed.deserialize(&mut *self.de)?;
+        // Parse the colon separating map key from value.
+        self.de.expect(':', ErrorCode::ExpectedMapColon)?;
+        Ok((val, self))
+    }
+}
+
+// `VariantAccess` is provided to the `Visitor` to give it the ability to see
+// the content of the single variant that it decided to deserialize.
+impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+
+    // If the `Visitor` expected this variant to be a unit variant, the input
+    // should have been the plain string case handled in `deserialize_enum`.
+    fn unit_variant(self) -> Result<()> {
+        Err(self.de.error(ErrorCode::ExpectedString))
+    }
+
+    // Newtype variants are represented in JSON as `{ NAME: VALUE }` so
+    // deserialize the value here.
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.de)
+    }
+
+    // Tuple variants are represented in JSON as `{ NAME: [DATA...] }` so
+    // deserialize the sequence of data here.
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    // Struct variants are represented in JSON as `{ NAME: { K: V, ... } }` so
+    // deserialize the inner map here.
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
 #[test]
 fn test_empty_object() {
     use std::collections::HashMap;
@@ -408,3 +1180,481 @@ fn test_struct() {
 
     assert_eq!(from_str::<Test>(json).unwrap(), expected);
 }
+
+#[test]
+fn test_primitives() {
+    assert!(from_str::<bool>("true").unwrap());
+    assert_eq!(from_str::<i8>("-12").unwrap(), -12);
+    assert_eq!(from_str::<i64>("42").unwrap(), 42);
+    assert_eq!(from_str::<u16>("65535").unwrap(), u16::MAX);
+    assert_eq!(from_str::<f32>("-0.5").unwrap(), -0.5);
+    assert_eq!(from_str::<f64>("1.5e3").unwrap(), 1500.0);
+    assert_eq!(from_str::<char>(r#""x""#).unwrap(), 'x');
+    assert!(matches!(
+        from_str::<char>(r#""xy""#).unwrap_err().code(),
+        ErrorCode::ExpectedChar
+    ));
+    assert!(matches!(
+        from_str::<u8>("x").unwrap_err().code(),
+        ErrorCode::ExpectedInteger
+    ));
+}
+
+#[test]
+fn test_numbers() {
+    use crate::Value;
+
+    assert_eq!(from_str::<i64>("-0").unwrap(), 0);
+    assert_eq!(from_str::<i8>("-128").unwrap(), i8::MIN);
+    assert_eq!(from_str::<u64>("18446744073709551615").unwrap(), u64::MAX);
+    assert_eq!(
+        from_str::<i128>("-170141183460469231731687303715884105728").unwrap(),
+        i128::MIN
+    );
+    assert_eq!(from_str::<f64>("0").unwrap(), 0.0);
+    assert_eq!(from_str::<f64>("-12.5E-1").unwrap(), -1.25);
+    assert_eq!(from_str::<f64>("1e+2").unwrap(), 100.0);
+    assert_eq!(from_str::<f32>("0.1").unwrap(), 0.1);
+
+    assert_eq!(from_str::<Value>("1").unwrap().as_u64(), Some(1));
+    assert_eq!(from_str::<Value>("-1").unwrap().as_i64(), Some(-1));
+    assert_eq!(from_str::<Value>("1.0").unwrap().as_f64(), Some(1.0));
+    assert_eq!(from_str::<Value>("1e2").unwrap().as_f64(), Some(100.0));
+    assert_eq!(
+        from_str::<Value>("18446744073709551616").unwrap().as_f64(),
+        Some(18446744073709551616.0)
+    );
+
+    for invalid in [
+        "01", "-01", "-", "-a", "1.", "1.e5", ".5", "1e", "1e+", "+1",
+    ] {
+        assert!(from_str::<f64>(invalid).is_err(), "{invalid}");
+    }
+    let err = from_str::<u32>("1.5").unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::ExpectedInteger));
+    assert_eq!(err.found(), Some('.'));
+    let err = from_str::<f64>("00").unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::InvalidNumber));
+    assert_eq!(err.column(), Some(2));
+    assert!(from_str::<f64>("1e").unwrap_err().is_eof());
+
+    let err = from_str::<u8>("256").unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::NumberOutOfRange("u8")));
+    assert_eq!(err.column(), Some(1));
+    assert!(err.to_string().starts_with("number out of range for `u8`"));
+    assert!(matches!(
+        from_str::<u32>("-1").unwrap_err().code(),
+        ErrorCode::NumberOutOfRange("u32")
+    ));
+    assert!(matches!(
+        from_str::<i64>("9223372036854775808").unwrap_err().code(),
+        ErrorCode::NumberOutOfRange("i64")
+    ));
+    assert!(matches!(
+        from_str::<f64>("1e400").unwrap_err().code(),
+        ErrorCode::NumberOutOfRange("f64")
+    ));
+    assert!(matches!(
+        from_str::<f32>("1e39").unwrap_err().code(),
+        ErrorCode::NumberOutOfRange("f32")
+    ));
+}
+
+#[test]
+fn test_option_unit_and_tuples() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Unit;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Meters(u32);
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Point(i32, i32);
+
+    assert_eq!(from_str::<Option<u32>>("null").unwrap(), None);
+    assert_eq!(from_str::<Option<u32>>("7").unwrap(), Some(7));
+    from_str::<()>("null").unwrap();
+    assert_eq!(from_str::<Unit>("null").unwrap(), Unit);
+    assert_eq!(from_str::<Meters>("3").unwrap(), Meters(3));
+    assert_eq!(from_str::<Point>("[1,-2]").unwrap(), Point(1, -2));
+    assert_eq!(
+        from_str::<(u8, String, bool)>(r#"[1,"a",false]"#).unwrap(),
+        (1, "a".to_string(), false)
+    );
+}
+
+#[test]
+fn test_enum() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum E {
+        Unit,
+        Newtype(u32),
+        Tuple(u32, u32),
+        Struct { a: u32 },
+    }
+
+    let j = r#""Unit""#;
+    assert_eq!(from_str::<E>(j).unwrap(), E::Unit);
+
+    let j = r#"{"Newtype":1}"#;
+    assert_eq!(from_str::<E>(j).unwrap(), E::Newtype(1));
+
+    let j = r#"{"Tuple":[1,2]}"#;
+    assert_eq!(from_str::<E>(j).unwrap(), E::Tuple(1, 2));
+
+    let j = r#"{"Struct":{"a":1}}"#;
+    assert_eq!(from_str::<E>(j).unwrap(), E::Struct { a: 1 });
+}
+
+#[test]
+fn test_map_keys() {
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    assert_eq!(
+        from_str::<BTreeMap<i32, u8>>(r#"{"1": 2, "-3": 4, "0": 5}"#).unwrap(),
+        BTreeMap::from([(1, 2), (-3, 4), (0, 5)])
+    );
+    assert_eq!(
+        from_str::<BTreeMap<u64, u8>>(r#"{"18446744073709551615":1}"#).unwrap(),
+        BTreeMap::from([(u64::MAX, 1)])
+    );
+    assert_eq!(
+        from_str::<BTreeMap<bool, u8>>(r#"{"true":1,"false":0}"#).unwrap(),
+        BTreeMap::from([(false, 0), (true, 1)])
+    );
+    assert_eq!(
+        from_str::<BTreeMap<char, u8>>(r#"{"x":1}"#).unwrap(),
+        BTreeMap::from([('x', 1)])
+    );
+    assert_eq!(
+        from_str::<BTreeMap<Color, u8>>(r#"{"Green":1}"#).unwrap(),
+        BTreeMap::from([(Color::Green, 1)])
+    );
+    assert_eq!(
+        from_str::<BTreeMap<Option<u8>, u8>>(r#"{"1":1}"#).unwrap(),
+        BTreeMap::from([(Some(1), 1)])
+    );
+
+    // The key has to be a JSON integer inside the quotes, nothing more.
+    for invalid in [
+        "1", r#""01""#, r#""+1""#, r#"" 1""#, r#""1 ""#, r#""""#, r#""1.0""#,
+    ] {
+        let json = format!("{{{invalid}:0}}");
+        assert!(from_str::<BTreeMap<u8, u8>>(&json).is_err(), "{json}");
+    }
+    let err = from_str::<BTreeMap<u8, u8>>(r#"{"1":0, "x":1}"#).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::ExpectedInteger));
+    assert_eq!(err.column(), Some(9));
+    let err = from_str::<BTreeMap<u8, u8>>(r#"{"256":0}"#).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::NumberOutOfRange("u8")));
+    assert!(from_str::<BTreeMap<bool, u8>>(r#"{"yes":0}"#).is_err());
+}
+
+#[test]
+fn test_ignored_any() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Test {
+        kept: u32,
+    }
+
+    let json = r#"{"skipped":{"a":[1,-2,3.5,"x",null,true]},"kept":1}"#;
+    assert_eq!(from_str::<Test>(json).unwrap(), Test { kept: 1 });
+}
+
+#[test]
+fn test_from_slice_and_reader() {
+    assert_eq!(from_slice::<Vec<u32>>(b"[1,2]").unwrap(), vec![1, 2]);
+    assert_eq!(from_slice::<&str>(br#""abc""#).unwrap(), "abc");
+    assert!(matches!(
+        from_slice::<String>(b"\"\xff\"").unwrap_err().code(),
+        ErrorCode::InvalidUtf8
+    ));
+
+    let reader = io::Cursor::new(r#"{"key":"val"}"#);
+    let map: std::collections::HashMap<String, String> = from_reader(reader).unwrap();
+    assert_eq!(map["key"], "val");
+}
+
+#[test]
+fn test_string_escapes() {
+    assert_eq!(
+        from_str::<String>(r#""a\"b\\c\/d\b\f\n\r\t""#).unwrap(),
+        "a\"b\\c/d\u{8}\u{c}\n\r\t"
+    );
+    assert_eq!(from_str::<String>(r#""é中""#).unwrap(), "é中");
+    assert_eq!(from_str::<String>(r#""😀""#).unwrap(), "😀");
+    assert_eq!(from_str::<char>(r#""\n""#).unwrap(), '\n');
+
+    // Escapes force an allocation, borrowing is only possible without them.
+    assert!(from_str::<&str>(r#""a\nb""#).is_err());
+    assert_eq!(from_str::<&str>(r#""plain""#).unwrap(), "plain");
+
+    assert!(matches!(
+        from_str::<String>(r#""\x""#).unwrap_err().code(),
+        ErrorCode::InvalidEscape
+    ));
+    assert!(matches!(
+        from_str::<String>(r#""\u12g4""#).unwrap_err().code(),
+        ErrorCode::InvalidEscape
+    ));
+    assert!(matches!(
+        from_str::<String>(r#""\ud83d""#).unwrap_err().code(),
+        ErrorCode::InvalidUnicodeCodePoint
+    ));
+    assert!(matches!(
+        from_str::<String>(r#""\ude00""#).unwrap_err().code(),
+        ErrorCode::InvalidUnicodeCodePoint
+    ));
+    assert!(matches!(
+        from_str::<String>("\"a\nb\"").unwrap_err().code(),
+        ErrorCode::ControlCharacterInString
+    ));
+}
+
+#[test]
+fn test_borrowed() {
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize)]
+    struct Log<'a> {
+        host: &'a str,
+        raw: &'a [u8],
+        #[serde(borrow)]
+        message: Cow<'a, str>,
+        #[serde(borrow)]
+        payload: Cow<'a, [u8]>,
+        tags: BTreeMap<&'a str, &'a str>,
+    }
+
+    // Whether `part` is a slice of `whole` rather than a copy of some of it.
+    fn points_into(whole: &str, part: &[u8]) -> bool {
+        whole.as_bytes().as_ptr_range().contains(&part.as_ptr())
+    }
+
+    let json =
+        r#"{"host":"db1","raw":"abc","message":"disk full","payload":"xyz","tags":{"env":"prod"}}"#;
+    let log: Log = from_str(json).unwrap();
+    assert_eq!(log.host, "db1");
+    assert!(points_into(json, log.host.as_bytes()));
+    assert_eq!(log.raw, b"abc");
+    assert!(points_into(json, log.raw));
+    assert!(matches!(log.message, Cow::Borrowed(_)));
+    assert!(points_into(json, log.message.as_bytes()));
+    assert!(matches!(log.payload, Cow::Borrowed(_)));
+    assert!(points_into(json, &log.payload));
+    let (key, value) = log.tags.iter().next().unwrap();
+    assert!(points_into(json, key.as_bytes()));
+    assert!(points_into(json, value.as_bytes()));
+
+    // Escapes leave nothing to borrow, `Cow` falls back to an owned copy.
+    let json =
+        r#"{"host":"db1","raw":"abc","message":"disk\tfull","payload":"x\u0079z","tags":{}}"#;
+    let log: Log = from_str(json).unwrap();
+    assert!(matches!(log.message, Cow::Owned(ref s) if s == "disk\tfull"));
+    assert!(matches!(log.payload, Cow::Owned(ref b) if b == b"xyz"));
+
+    // While a plain reference cannot hold the decoded text at all.
+    assert!(from_str::<&str>(r#""a\nb""#).is_err());
+    assert!(from_str::<&[u8]>(r#""a\nb""#).is_err());
+
+    let bytes = br#"["abc"]"#;
+    let v: Vec<&str> = from_slice(bytes).unwrap();
+    assert!(bytes.as_ptr_range().contains(&v[0].as_ptr()));
+}
+
+#[test]
+fn test_limits() {
+    use crate::Value;
+    use std::collections::BTreeMap;
+
+    // Far deeper than the stack could take without the depth limit.
+    let deep = "[".repeat(100_000);
+    let err = from_str::<Value>(&deep).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::RecursionLimitExceeded));
+    assert_eq!(err.column(), Some(129));
+    let deep = format!("{}{}", r#"{"a":"#.repeat(200), "1}".repeat(200));
+    assert!(matches!(
+        from_str::<Value>(&deep).unwrap_err().code(),
+        ErrorCode::RecursionLimitExceeded
+    ));
+
+    let limits = Limits {
+        max_depth: 2,
+        max_string_len: 3,
+        max_collection_len: 2,
+    };
+    assert!(from_str_with_limits::<Value>("[[1]]", limits).is_ok());
+    // At the limit, a value of the wrong type is still a type error.
+    let err = from_str_with_limits::<Vec<Vec<Vec<u8>>>>("[[ 1]]", limits).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::ExpectedArray));
+    assert_eq!(err.column(), Some(4));
+    let err = from_str_with_limits::<Vec<Vec<BTreeMap<String, u8>>>>("[[1]]", limits).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::ExpectedMap));
+    assert_eq!(err.column(), Some(3));
+    let err = from_str_with_limits::<Vec<Vec<Vec<u8>>>>("[[ []]]", limits).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::RecursionLimitExceeded));
+    assert_eq!(err.column(), Some(4));
+    assert!(matches!(
+        from_str_with_limits::<Value>("[{}, [[]]]", limits)
+            .unwrap_err()
+            .code(),
+        ErrorCode::RecursionLimitExceeded
+    ));
+
+    assert_eq!(
+        from_str_with_limits::<&str>(r#""abc""#, limits).unwrap(),
+        "abc"
+    );
+    let err = from_str_with_limits::<&str>(r#"  "abcd""#, limits).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::StringTooLong));
+    assert_eq!(err.column(), Some(3));
+    assert_eq!(
+        from_str_with_limits::<String>(r#""a\nb""#, limits).unwrap(),
+        "a\nb"
+    );
+    assert!(matches!(
+        from_str_with_limits::<String>(r#""a\nbc""#, limits)
+            .unwrap_err()
+            .code(),
+        ErrorCode::StringTooLong
+    ));
+    // Keys are strings too.
+    assert!(matches!(
+        from_str_with_limits::<BTreeMap<String, u8>>(r#"{"long":1}"#, limits)
+            .unwrap_err()
+            .code(),
+        ErrorCode::StringTooLong
+    ));
+
+    assert!(from_str_with_limits::<Vec<u8>>("[1,2]", limits).is_ok());
+    let err = from_str_with_limits::<Vec<u8>>("[1,2,3]", limits).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::CollectionTooLarge));
+    assert_eq!(err.column(), Some(6));
+    assert!(matches!(
+        from_str_with_limits::<Value>(r#"{"a":1,"b":2,"c":3}"#, limits)
+            .unwrap_err()
+            .code(),
+        ErrorCode::CollectionTooLarge
+    ));
+}
+
+#[test]
+fn test_whitespace() {
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum E {
+        Unit,
+        Tuple(u32, u32),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Test {
+        a: Option<i32>,
+        b: Vec<f64>,
+        c: BTreeMap<String, bool>,
+        d: Vec<E>,
+        e: (),
+    }
+
+    let json = "
+        {
+            \"a\" : -1 ,
+            \"b\" :\t[ 1.5 , 2 ]\r\n,
+            \"c\": { \"x\" : true, \"y\":false },
+            \"d\": [ \"Unit\", { \"Tuple\" : [ 1 , 2 ] } ],
+            \"e\": null
+        }
+    ";
+    let expected = Test {
+        a: Some(-1),
+        b: vec![1.5, 2.0],
+        c: BTreeMap::from([("x".to_string(), true), ("y".to_string(), false)]),
+        d: vec![E::Unit, E::Tuple(1, 2)],
+        e: (),
+    };
+    assert_eq!(from_str::<Test>(json).unwrap(), expected);
+    assert_eq!(from_str::<Option<u8>>(" null ").unwrap(), None);
+    assert_eq!(from_str::<String>(" \" a b \" ").unwrap(), " a b ");
+    assert!(matches!(
+        from_str::<u8>("1 2").unwrap_err().code(),
+        ErrorCode::TrailingCharacters
+    ));
+}
+
+#[test]
+fn test_error_position() {
+    use crate::error::{Category, Position};
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Test {
+        a: u32,
+        b: u32,
+    }
+
+    let err = from_str::<Test>("{\n  \"a\": 1,\n  \"b\" 2\n}").unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::ExpectedMapColon));
+    assert_eq!(
+        err.position(),
+        Some(Position {
+            offset: 18,
+            line: 3,
+            column: 7
+        })
+    );
+    assert_eq!(err.found(), Some('2'));
+    assert_eq!(err.classify(), Category::Syntax);
+    assert_eq!(
+        err.to_string(),
+        "expected `:` after object key, found '2' at line 3 column 7\n  |\n3 |   \"b\" 2\n  |       ^"
+    );
+
+    let err = from_str::<Test>(r#"{"a":1}"#).unwrap_err();
+    assert_eq!(err.classify(), Category::Data);
+    assert_eq!(err.column(), Some(7));
+    assert!(
+        err.to_string()
+            .starts_with("missing field `b` at line 1 column 7")
+    );
+
+    let err = from_str::<Test>(r#"{"a":"#).unwrap_err();
+    assert!(err.is_eof());
+    assert!(
+        err.to_string()
+            .starts_with("unexpected end of input at line 1 column 6")
+    );
+
+    let err = from_slice::<String>(b"\"ab\xff\"").unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::InvalidUtf8));
+    assert_eq!(err.column(), Some(4));
+
+    let err = from_reader::<_, u32>(FailingReader).unwrap_err();
+    assert!(err.is_io());
+    assert!(err.position().is_none());
+
+    struct FailingReader;
+
+    impl io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("boom"))
+        }
+    }
+}
+
+#[test]
+fn test_error_snippet_is_truncated() {
+    let json = format!("[{}x]", "1,".repeat(100));
+    let err = from_str::<Vec<u32>>(&json).unwrap_err();
+    assert_eq!(err.column(), Some(202));
+    let snippet = err.to_string();
+    let lines: Vec<&str> = snippet.lines().collect();
+    assert_eq!(lines[2], format!("1 | ...{}x]", "1,".repeat(20)));
+    assert_eq!(lines[3], format!("  | {}^", " ".repeat(43)));
+}
@@ -0,0 +1,242 @@
+use super::Deserializer;
+use crate::bytes::BytesEncoding;
+use crate::error::{Error, ErrorCode, Result};
+use serde::de::{Deserialize, DeserializeOwned};
+use std::io;
+
+// Bounds on what the deserializer is willing to parse, for input that cannot
+// be trusted. Each one has its own error code when it is exceeded.
+//
+// Nesting is limited by default because arrays and objects are parsed by
+// recursion, so something like `[[[[...` a hundred thousand levels deep would
+// otherwise overflow the stack. Strings and collections are unlimited unless
+// asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    // How many arrays and objects may be nested inside each other.
+    pub max_depth: usize,
+    // The longest string, in bytes after escapes are decoded.
+    pub max_string_len: usize,
+    // The most elements in one array or entries in one object.
+    pub max_collection_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 128,
+            max_string_len: usize::MAX,
+            max_collection_len: usize::MAX,
+        }
+    }
+}
+
+// How the deserializer reads its input. The default is strict RFC 8259 JSON,
+// each extension has to be switched on:
+//
+//     let config: Config = DeserializerOptions::new()
+//         .allow_comments(true)
+//         .allow_trailing_commas(true)
+//         .from_str(text)?;
+//
+// The extensions are a small part of JSON5, enough for files that people edit
+// by hand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeserializerOptions {
+    pub(super) comments: bool,
+    pub(super) trailing_commas: bool,
+    pub(super) single_quotes: bool,
+    pub(super) nan_and_infinity: bool,
+    pub(super) bytes: BytesEncoding,
+    pub(super) limits: Limits,
+}
+
+impl DeserializerOptions {
+    // Strict JSON with the default limits.
+    pub fn new() -> Self {
+        DeserializerOptions::default()
+    }
+
+    // `// line` and `/* block */` comments wherever whitespace may appear.
+    pub fn allow_comments(mut self, allow: bool) -> Self {
+        self.comments = allow;
+        self
+    }
+
+    // A comma after the last element of an array or entry of an object,
+    // `[1, 2,]`. A comma on its own, `[,]`, is still an error.
+    pub fn allow_trailing_commas(mut self, allow: bool) -> Self {
+        self.trailing_commas = allow;
+        self
+    }
+
+    // Strings in single quotes, `'it\'s'`. Inside them `"` needs no escape
+    // and `\'` is an escape for `'`.
+    pub fn allow_single_quotes(mut self, allow: bool) -> Self {
+        self.single_quotes = allow;
+        self
+    }
+
+    // The literals `NaN`, `Infinity` and `-Infinity` wherever a float is
+    // expected. `Value` has no way to hold them and turns them into `null`.
+    pub fn allow_nan_and_infinity(mut self, allow: bool) -> Self {
+        self.nan_and_infinity = allow;
+        self
+    }
+
+    // How byte strings are encoded, which has to match what the serializer
+    // was told. With `BytesEncoding::Array`, the default, a plain string is
+    // accepted as well and read as its UTF-8 bytes.
+    pub fn bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes = encoding;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn deserializer<'de>(&self, input: &'de str) -> Deserializer<'de> {
+        Deserializer::with_options(input, *self)
+    }
+
+    // All entry points end up here, the byte oriented ones only have to get
+    // hold of a `&str` first. See the free function `from_str` for which
+    // values can borrow from `s`.
+    pub fn from_str<'a, T: Deserialize<'a>>(&self, s: &'a str) -> Result<T> {
+        let mut deserializer = self.deserializer(s);
+        let t = T::deserialize(&mut deserializer).map_err(|err| deserializer.fix_position(err))?;
+        deserializer.skip_whitespace();
+        if deserializer.input.is_empty() {
+            Ok(t)
+        } else {
+            Err(deserializer.error(ErrorCode::TrailingCharacters))
+        }
+    }
+
+    // JSON text is UTF-8, so the bytes are validated once up front and the
+    // rest of the parser keeps working on `&str`.
+    pub fn from_slice<'a, T: Deserialize<'a>>(&self, v: &'a [u8]) -> Result<T> {
+        let s = std::str::from_utf8(v).map_err(|err| {
+            // Only the valid prefix can be shown, the error sits right after it.
+            let valid = &v[..err.valid_up_to()];
+            let valid = std::str::from_utf8(valid).unwrap_or_default();
+            Error::at(ErrorCode::InvalidUtf8, valid, valid.len())
+        })?;
+        self.from_str(s)
+    }
+
+    // The reader is drained into a buffer that does not outlive this call, so
+    // the result cannot borrow from the input and must be `DeserializeOwned`.
+    pub fn from_reader<R: io::Read, T: DeserializeOwned>(&self, mut reader: R) -> Result<T> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        self.from_slice(&buf)
+    }
+}
+
+#[test]
+fn test_options() {
+    use crate::Value;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        ports: Vec<u16>,
+        ratio: f64,
+    }
+
+    let text = "
+        // Hand-edited, so comments and trailing commas are welcome.
+        {
+            'name': 'it\\'s \"quoted\"', /* single quotes too */
+            \"ports\": [80, 443,],
+            \"ratio\": -Infinity,
+        }
+        // done
+    ";
+    let expected = Config {
+        name: "it's \"quoted\"".to_string(),
+        ports: vec![80, 443],
+        ratio: f64::NEG_INFINITY,
+    };
+    let options = DeserializerOptions::new()
+        .allow_comments(true)
+        .allow_trailing_commas(true)
+        .allow_single_quotes(true)
+        .allow_nan_and_infinity(true);
+    assert_eq!(options.from_str::<Config>(text).unwrap(), expected);
+    assert_eq!(
+        options.from_slice::<Config>(text.as_bytes()).unwrap(),
+        expected
+    );
+
+    // Strict is the default, each extension on its own is still rejected.
+    assert!(crate::from_str::<Config>(text).is_err());
+    assert!(crate::from_str::<Value>("[1] // comment").is_err());
+    assert!(crate::from_str::<Value>("[1,]").is_err());
+    assert!(crate::from_str::<Value>("'a'").is_err());
+    assert!(crate::from_str::<Value>("NaN").is_err());
+    let strict = DeserializerOptions::new();
+    assert!(
+        strict
+            .allow_comments(true)
+            .from_str::<Value>("[1,]")
+            .is_err()
+    );
+    assert!(
+        strict
+            .allow_trailing_commas(true)
+            .from_str::<Value>("'a'")
+            .is_err()
+    );
+
+    let options = DeserializerOptions::new().allow_comments(true);
+    assert_eq!(
+        options
+            .from_str::<Vec<u8>>("[1, // one\n 2 /* two */]")
+            .unwrap(),
+        [1, 2]
+    );
+    assert!(
+        options
+            .from_str::<Vec<u8>>("[1 /* open")
+            .unwrap_err()
+            .is_eof()
+    );
+    assert!(options.from_str::<Vec<u8>>("[1 / 2]").is_err());
+
+    let options = DeserializerOptions::new().allow_trailing_commas(true);
+    assert_eq!(options.from_str::<Vec<u8>>("[1, 2 , ]").unwrap(), [1, 2]);
+    assert!(options.from_str::<Vec<u8>>("[,]").is_err());
+    assert!(options.from_str::<Vec<u8>>("[1,,]").is_err());
+    assert_eq!(
+        options.from_str::<Value>(r#"{"a":{},}"#).unwrap(),
+        crate::json!({ "a": {} })
+    );
+
+    let options = DeserializerOptions::new().allow_single_quotes(true);
+    assert_eq!(options.from_str::<&str>("'a\"b'").unwrap(), "a\"b");
+    assert_eq!(
+        options.from_str::<Value>("['a']").unwrap(),
+        crate::json!(["a"])
+    );
+    assert!(options.from_str::<&str>("'a\"").is_err());
+    assert!(crate::from_str::<&str>(r#""\'""#).is_err());
+
+    let options = DeserializerOptions::new().allow_nan_and_infinity(true);
+    assert!(options.from_str::<f64>("NaN").unwrap().is_nan());
+    assert_eq!(options.from_str::<f32>(" Infinity").unwrap(), f32::INFINITY);
+    assert_eq!(
+        options.from_str::<Vec<f64>>("[-Infinity, 1]").unwrap(),
+        [f64::NEG_INFINITY, 1.0]
+    );
+    assert_eq!(
+        options.from_str::<Value>("[NaN, -1]").unwrap(),
+        crate::json!([null, -1])
+    );
+    assert!(options.from_str::<u32>("NaN").is_err());
+    assert!(options.from_str::<f64>("nan").is_err());
+}
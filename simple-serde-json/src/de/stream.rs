@@ -0,0 +1,387 @@
+use super::Deserializer;
+use crate::error::{Error, ErrorCode, Result};
+use serde::de::{Deserialize, DeserializeOwned};
+use std::io::{self, BufRead, BufReader};
+use std::marker::PhantomData;
+
+impl<'de> Deserializer<'de> {
+    // Turn the deserializer into an iterator over the values that follow one
+    // another in the input. This can't be `IntoIterator`, which has no room
+    // for the type of the values.
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<T: Deserialize<'de>>(self) -> StreamDeserializer<'de, T> {
+        StreamDeserializer {
+            de: self,
+            value_offset: 0,
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    // Skip whitespace and parse the value after it, returning where the value
+    // started along with it. `None` means there is nothing left but
+    // whitespace.
+    fn next_value<T: Deserialize<'de>>(&mut self) -> Option<Result<(usize, T)>> {
+        self.skip_whitespace();
+        if self.input.is_empty() {
+            return None;
+        }
+        let start = self.offset();
+        Some(
+            T::deserialize(&mut *self)
+                .and_then(|value| self.end_of_value().map(|()| (start, value)))
+                .map_err(|err| self.fix_position(err)),
+        )
+    }
+
+    // Check that the value just parsed is over. Objects, arrays and strings
+    // end with a closing character, anything else has to be followed by
+    // whitespace, the end of the input or a value that starts with one, or
+    // `truefalse` would be read as two values.
+    fn end_of_value(&self) -> Result<()> {
+        let closed = |ch| matches!(ch, '}' | ']') || self.is_quote(ch);
+        let opens = |ch| matches!(ch, '{' | '[') || self.is_quote(ch);
+        let parsed = &self.original[..self.offset()];
+        if parsed.chars().next_back().is_some_and(closed) {
+            return Ok(());
+        }
+        match self.input.chars().next() {
+            None | Some(' ' | '\t' | '\n' | '\r') => Ok(()),
+            Some('/') if self.options.comments => Ok(()),
+            Some(ch) if opens(ch) => Ok(()),
+            Some(_) => Err(self.error(ErrorCode::TrailingCharacters)),
+        }
+    }
+}
+
+// Iterates over a sequence of JSON values, as found in newline-delimited JSON
+// or a stream of concatenated documents:
+//
+//     {"level":"info"}
+//     {"level":"warn"} {"level":"error"}
+//
+// Values may be separated by any amount of whitespace. None is needed next to
+// an object, array or string, as between two objects above, but other values
+// have to be kept apart: `1true` is an error rather than `1` and `true`. The
+// first error ends the iteration, there is no telling where the next value
+// would start.
+pub struct StreamDeserializer<'de, T> {
+    de: Deserializer<'de>,
+    value_offset: usize,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> StreamDeserializer<'de, T> {
+    pub fn new(input: &'de str) -> Self {
+        Deserializer::from_str(input).into_iter()
+    }
+
+    // The byte offset in the input at which the value most recently returned
+    // by `next` started.
+    pub fn value_offset(&self) -> usize {
+        self.value_offset
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Iterator for StreamDeserializer<'de, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+        match self.de.next_value()? {
+            Ok((start, value)) => {
+                self.value_offset = start;
+                Some(Ok(value))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// The same as `StreamDeserializer` but reading the values from an
+// `io::Read`, so the values cannot borrow from the input.
+//
+// The input is read a line at a time. JSON tokens never span a line break, so
+// whenever the buffer ends on one, a value that runs into the end of the
+// buffer is incomplete rather than malformed and more lines are read before
+// trying again. Lines are dropped from the buffer once every value on them has
+// been returned, so memory use is bounded by the largest value rather than the
+// whole stream.
+pub struct ReaderStreamDeserializer<R, T> {
+    reader: BufReader<R>,
+    // Complete lines of input, except at the end of the input.
+    buf: Vec<u8>,
+    // How much of `buf` has been parsed.
+    consumed: usize,
+    // Bytes and lines dropped from the front of `buf`, to report positions
+    // relative to the whole input.
+    dropped_bytes: usize,
+    dropped_lines: usize,
+    value_offset: usize,
+    eof: bool,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<R: io::Read, T: DeserializeOwned> ReaderStreamDeserializer<R, T> {
+    pub fn new(reader: R) -> Self {
+        ReaderStreamDeserializer {
+            reader: BufReader::new(reader),
+            buf: Vec::new(),
+            consumed: 0,
+            dropped_bytes: 0,
+            dropped_lines: 0,
+            value_offset: 0,
+            eof: false,
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    // The byte offset in the input at which the value most recently returned
+    // by `next` started.
+    pub fn value_offset(&self) -> usize {
+        self.value_offset
+    }
+
+    // Read at least one more line, and keep going until the buffer has doubled
+    // in size. Re-parsing a value that spans many lines after every single
+    // line would take quadratic time.
+    fn fill(&mut self) -> io::Result<()> {
+        let target = self.buf.len() * 2;
+        loop {
+            if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+                self.eof = true;
+                return Ok(());
+            }
+            if self.buf.len() >= target {
+                return Ok(());
+            }
+        }
+    }
+
+    // Try to parse the next value from what has been read so far. Returns the
+    // value's offset in `buf` and how far parsing got.
+    //
+    // Input after invalid UTF-8 is out of reach, but the values before it are
+    // still returned.
+    fn parse_buffered(&self) -> Option<Result<(usize, T, usize)>> {
+        let (input, invalid) = match std::str::from_utf8(&self.buf) {
+            Ok(input) => (input, false),
+            Err(err) => {
+                let valid = &self.buf[..err.valid_up_to()];
+                (std::str::from_utf8(valid).unwrap_or_default(), true)
+            }
+        };
+        let mut de = Deserializer::from_str(input);
+        de.input = &input[self.consumed..];
+        match de.next_value() {
+            Some(Ok((start, value))) => Some(Ok((start, value, de.offset()))),
+            Some(Err(err)) if !(invalid && err.is_eof()) => Some(Err(err)),
+            None if !invalid => None,
+            _ => Some(Err(Error::at(ErrorCode::InvalidUtf8, input, input.len()))),
+        }
+    }
+
+    // Drop the lines that have been parsed completely from the front of
+    // `buf`. The current line stays so that error snippets can show it.
+    fn drop_parsed_lines(&mut self) {
+        if let Some(newline) = self.buf[..self.consumed].iter().rposition(|&b| b == b'\n') {
+            let len = newline + 1;
+            self.dropped_lines += self.buf[..len].iter().filter(|&&b| b == b'\n').count();
+            self.dropped_bytes += len;
+            self.consumed -= len;
+            self.buf.drain(..len);
+        }
+    }
+
+    fn fail(&mut self, mut err: Error) -> Option<Result<T>> {
+        self.failed = true;
+        err.shift_position(self.dropped_bytes, self.dropped_lines);
+        Some(Err(err))
+    }
+}
+
+impl<R: io::Read, T: DeserializeOwned> Iterator for ReaderStreamDeserializer<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            match self.parse_buffered() {
+                Some(Ok((start, value, end))) => {
+                    self.value_offset = self.dropped_bytes + start;
+                    self.consumed = end;
+                    self.drop_parsed_lines();
+                    return Some(Ok(value));
+                }
+                // Running out of input only means the value is not complete
+                // yet, unless the reader has nothing more to give.
+                Some(Err(err)) if !err.is_eof() || self.eof => return self.fail(err),
+                None if self.eof => return None,
+                _ => {}
+            }
+            if let Err(err) = self.fill() {
+                return self.fail(err.into());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stream() {
+    use crate::Value;
+
+    let input = "{\"a\":1}\n{\"a\":2} {\"a\":3}{\"a\":4}\n\n  [5]\"six\" 7 \n";
+    let mut stream = StreamDeserializer::<Value>::new(input);
+    let mut values = Vec::new();
+    while let Some(value) = stream.next() {
+        values.push((stream.value_offset(), value.unwrap().to_string()));
+    }
+    assert_eq!(
+        values,
+        [
+            (0, r#"{"a":1}"#.to_string()),
+            (8, r#"{"a":2}"#.to_string()),
+            (16, r#"{"a":3}"#.to_string()),
+            (23, r#"{"a":4}"#.to_string()),
+            (34, "[5]".to_string()),
+            (37, r#""six""#.to_string()),
+            (43, "7".to_string()),
+        ]
+    );
+
+    // The first error ends the stream.
+    let mut stream = Deserializer::from_str("1 2 x 4").into_iter::<u32>();
+    assert_eq!(stream.next().unwrap().unwrap(), 1);
+    assert_eq!(stream.next().unwrap().unwrap(), 2);
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.column(), Some(5));
+    assert!(stream.next().is_none());
+
+    // Values borrow from the input like with `from_str`.
+    let input = r#""a" "b""#;
+    let strs: Vec<&str> = StreamDeserializer::new(input)
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(strs, ["a", "b"]);
+    assert!(input.as_bytes().as_ptr_range().contains(&strs[1].as_ptr()));
+
+    assert!(StreamDeserializer::<Value>::new(" \n ").next().is_none());
+
+    // Values other than objects, arrays and strings need a separator.
+    let values: Vec<Value> = StreamDeserializer::new(r#"1[2]3{"a":4}true"5"null"#)
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(values.len(), 7);
+    for input in [
+        "truefalse",
+        "nulltrue",
+        "1true",
+        "1.5null",
+        "true1",
+        "false-1",
+    ] {
+        let mut stream = StreamDeserializer::<Value>::new(input);
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(
+            matches!(err.code(), ErrorCode::TrailingCharacters),
+            "{input}"
+        );
+        assert!(stream.next().is_none());
+    }
+    let err = Deserializer::from_str("1 2x")
+        .into_iter::<u32>()
+        .nth(1)
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::TrailingCharacters));
+    assert_eq!(err.column(), Some(4));
+}
+
+#[test]
+fn test_reader_stream() {
+    use crate::{Position, Value};
+
+    // Hands out the input a few bytes at a time, so values and lines arrive
+    // in pieces.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    fn collect(input: &[u8]) -> Vec<(usize, Result<Value>)> {
+        let mut stream = ReaderStreamDeserializer::new(Trickle(input));
+        let mut values = Vec::new();
+        while let Some(value) = stream.next() {
+            values.push((stream.value_offset(), value));
+        }
+        values
+    }
+
+    let input = "{\"a\":1}\n{\"a\":2} {\"a\":3}\n{\n  \"b\": [\n    12345\n  ]\n}\n-12.5e1";
+    let values = collect(input.as_bytes());
+    let values: Vec<_> = values
+        .into_iter()
+        .map(|(offset, value)| (offset, value.unwrap().to_string()))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            (0, r#"{"a":1}"#.to_string()),
+            (8, r#"{"a":2}"#.to_string()),
+            (16, r#"{"a":3}"#.to_string()),
+            (24, r#"{"b":[12345]}"#.to_string()),
+            (51, "-125.0".to_string()),
+        ]
+    );
+
+    // Errors are positioned in the whole input, not in what is left of it.
+    let mut values = collect(b"1\n2\n3 [4,\n 5 6]\n7\n");
+    assert_eq!(values.len(), 4);
+    let err = values.pop().unwrap().1.unwrap_err();
+    assert_eq!(
+        err.position(),
+        Some(Position {
+            offset: 13,
+            line: 4,
+            column: 4
+        })
+    );
+    assert!(err.to_string().ends_with("4 |  5 6]\n  |    ^"));
+
+    // A value cut short by the end of the input.
+    let mut values = collect(b"1\n[2,");
+    assert!(values.pop().unwrap().1.unwrap_err().is_eof());
+    assert_eq!(values.len(), 1);
+
+    // Values before invalid UTF-8 still come through.
+    let mut values = collect(b"1\n2\n\"\xff\"\n");
+    let err = values.pop().unwrap().1.unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::InvalidUtf8));
+    assert_eq!(err.line(), Some(3));
+    assert_eq!(values.len(), 2);
+
+    assert!(collect(b"").is_empty());
+    assert!(collect(b" \n\n ").is_empty());
+
+    let mut values = collect(b"1\n2true\n");
+    let err = values.pop().unwrap().1.unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::TrailingCharacters));
+    assert_eq!(err.line(), Some(2));
+    assert_eq!(values.len(), 1);
+}
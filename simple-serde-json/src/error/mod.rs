@@ -1,18 +1,270 @@
 use serde::{de, ser};
 use std::fmt::{self, Display};
+use std::io;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+// How many characters of the offending line are shown on either side of the
+// caret. Minified documents are a single huge line, so printing all of it
+// would bury the interesting part.
+const SNIPPET_CONTEXT: usize = 40;
+
+// The error is boxed so that `Result<T>` stays small on the happy path.
+#[derive(Debug)]
+pub struct Error {
+    err: Box<ErrorImpl>,
+}
+
+#[derive(Debug)]
+struct ErrorImpl {
+    code: ErrorCode,
+    location: Option<Location>,
+}
+
+// Everything needed to point at the fault without keeping the input alive.
+// Only the part of the line that is shown is kept, not all of it.
+#[derive(Debug)]
+struct Location {
+    position: Position,
+    found: Option<char>,
+    // Up to `2 * SNIPPET_CONTEXT` characters of the line around the fault.
+    snippet: String,
+    // The column of the first character of `snippet`.
+    snippet_column: usize,
+    // Whether the line goes on after `snippet`.
+    truncated: bool,
+}
+
+// Where in the input an error was detected. `offset` counts bytes from the
+// start of the input, `line` and `column` are 1-based and count characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+// The broad kind of an error, for callers that only need to decide how to
+// react to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    // Reading the input failed.
+    Io,
+    // The input is not valid JSON.
+    Syntax,
+    // The input is valid JSON but does not have the shape of the requested
+    // type, e.g. a string where an integer was expected or a missing field.
+    Data,
+    // The input ended in the middle of a value.
+    Eof,
+}
+
 #[derive(Debug)]
-pub enum Error {
+pub enum ErrorCode {
     Message(String),
+    Io(io::Error),
+    InvalidUtf8,
     Eof,
+    Syntax,
+    ExpectedBoolean,
+    ExpectedInteger,
+    ExpectedFloat,
+    InvalidNumber,
+    NumberOutOfRange(&'static str),
+    FloatMustBeFinite,
+    ExpectedChar,
     ExpectedString,
+    InvalidEscape,
+    InvalidUnicodeCodePoint,
+    ControlCharacterInString,
+    ExpectedBytes,
+    InvalidBase64,
+    ExpectedNull,
+    ExpectedArray,
+    ExpectedArrayComma,
+    ExpectedArrayEnd,
     ExpectedMap,
     ExpectedMapComma,
     ExpectedMapColon,
     ExpectedMapEnd,
+    ExpectedEnum,
+    KeyMustBeAString,
     TrailingCharacters,
+    RecursionLimitExceeded,
+    StringTooLong,
+    CollectionTooLarge,
+}
+
+impl Error {
+    // An error that is not tied to a place in the input, e.g. one raised by a
+    // `Serialize` impl.
+    pub(crate) fn new(code: ErrorCode) -> Self {
+        Error {
+            err: Box::new(ErrorImpl {
+                code,
+                location: None,
+            }),
+        }
+    }
+
+    // An error detected at byte `offset` of `input`.
+    pub(crate) fn at(code: ErrorCode, input: &str, offset: usize) -> Self {
+        let mut error = Error::new(code);
+        error.set_location(input, offset);
+        error
+    }
+
+    pub(crate) fn has_position(&self) -> bool {
+        self.err.location.is_some()
+    }
+
+    pub(crate) fn set_location(&mut self, input: &str, offset: usize) {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+
+        // Cut a window around the fault out of the line, counting characters
+        // so that multi-byte text lines up.
+        let skip = (column - 1).saturating_sub(SNIPPET_CONTEXT);
+        let mut chars = input[line_start..]
+            .chars()
+            .skip(skip)
+            .take_while(|&ch| ch != '\n');
+        let mut snippet: String = chars.by_ref().take(2 * SNIPPET_CONTEXT).collect();
+        // A `\r` before the newline is not part of the line.
+        let truncated = chars
+            .next()
+            .is_some_and(|ch| ch != '\r' || chars.next().is_some());
+        if !truncated && snippet.ends_with('\r') {
+            snippet.pop();
+        }
+
+        self.err.location = Some(Location {
+            position: Position {
+                offset,
+                line: before.matches('\n').count() + 1,
+                column,
+            },
+            found: input[offset..].chars().next(),
+            snippet,
+            snippet_column: skip + 1,
+            truncated,
+        });
+    }
+
+    // Move the position forward for an error found in a window of the input
+    // that starts `offset` bytes and `lines` lines into it. The window has to
+    // start at the beginning of a line, so the column stays the same.
+    pub(crate) fn shift_position(&mut self, offset: usize, lines: usize) {
+        if let Some(location) = &mut self.err.location {
+            location.position.offset += offset;
+            location.position.line += lines;
+        }
+    }
+
+    pub fn code(&self) -> &ErrorCode {
+        &self.err.code
+    }
+
+    // Where the error was detected, if it came from parsing input.
+    pub fn position(&self) -> Option<Position> {
+        self.err.location.as_ref().map(|location| location.position)
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.position().map(|position| position.line)
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        self.position().map(|position| position.column)
+    }
+
+    // The character at the error position, `None` at the end of input or for
+    // errors without a position.
+    pub fn found(&self) -> Option<char> {
+        self.err
+            .location
+            .as_ref()
+            .and_then(|location| location.found)
+    }
+
+    pub fn classify(&self) -> Category {
+        match self.err.code {
+            ErrorCode::Io(_) => Category::Io,
+            ErrorCode::Eof => Category::Eof,
+            ErrorCode::Message(_)
+            | ErrorCode::ExpectedBoolean
+            | ErrorCode::ExpectedInteger
+            | ErrorCode::ExpectedFloat
+            | ErrorCode::NumberOutOfRange(_)
+            | ErrorCode::FloatMustBeFinite
+            | ErrorCode::ExpectedChar
+            | ErrorCode::ExpectedString
+            | ErrorCode::ExpectedBytes
+            | ErrorCode::InvalidBase64
+            | ErrorCode::ExpectedNull
+            | ErrorCode::ExpectedArray
+            | ErrorCode::ExpectedMap
+            | ErrorCode::ExpectedEnum
+            | ErrorCode::KeyMustBeAString
+            | ErrorCode::RecursionLimitExceeded
+            | ErrorCode::StringTooLong
+            | ErrorCode::CollectionTooLarge => Category::Data,
+            ErrorCode::InvalidUtf8
+            | ErrorCode::Syntax
+            | ErrorCode::InvalidNumber
+            | ErrorCode::InvalidEscape
+            | ErrorCode::InvalidUnicodeCodePoint
+            | ErrorCode::ControlCharacterInString
+            | ErrorCode::ExpectedArrayComma
+            | ErrorCode::ExpectedArrayEnd
+            | ErrorCode::ExpectedMapComma
+            | ErrorCode::ExpectedMapColon
+            | ErrorCode::ExpectedMapEnd
+            | ErrorCode::TrailingCharacters => Category::Syntax,
+        }
+    }
+
+    pub fn is_io(&self) -> bool {
+        self.classify() == Category::Io
+    }
+
+    pub fn is_syntax(&self) -> bool {
+        self.classify() == Category::Syntax
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.classify() == Category::Data
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.classify() == Category::Eof
+    }
+}
+
+impl ErrorCode {
+    // Whether the message names the token the parser was looking for, so that
+    // it reads well followed by ", found ...".
+    fn is_expectation(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::Syntax
+                | ErrorCode::ExpectedBoolean
+                | ErrorCode::ExpectedInteger
+                | ErrorCode::ExpectedFloat
+                | ErrorCode::ExpectedString
+                | ErrorCode::ExpectedBytes
+                | ErrorCode::ExpectedNull
+                | ErrorCode::ExpectedArray
+                | ErrorCode::ExpectedArrayComma
+                | ErrorCode::ExpectedArrayEnd
+                | ErrorCode::ExpectedMap
+                | ErrorCode::ExpectedMapComma
+                | ErrorCode::ExpectedMapColon
+                | ErrorCode::ExpectedMapEnd
+                | ErrorCode::ExpectedEnum
+        )
+    }
 }
 
 impl ser::Error for Error {
@@ -20,7 +272,7 @@ impl ser::Error for Error {
     where
         T: Display,
     {
-        Error::Message(msg.to_string())
+        Error::new(ErrorCode::Message(msg.to_string()))
     }
 }
 
@@ -29,18 +281,133 @@ impl de::Error for Error {
     where
         T: Display,
     {
-        Error::Message(msg.to_string())
+        Error::new(ErrorCode::Message(msg.to_string()))
     }
 }
 
-impl Display for Error {
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::new(ErrorCode::Io(err))
+    }
+}
+
+impl Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::Message(msg) => write!(f, "{msg}"),
-            Error::TrailingCharacters => write!(f, "trailing characters"),
-            _ => write!(f, "EOF"),
+            ErrorCode::Message(msg) => write!(f, "{msg}"),
+            ErrorCode::Io(err) => write!(f, "io error: {err}"),
+            ErrorCode::InvalidUtf8 => write!(f, "input is not valid UTF-8"),
+            ErrorCode::Eof => write!(f, "unexpected end of input"),
+            ErrorCode::Syntax => write!(f, "expected value"),
+            ErrorCode::ExpectedBoolean => write!(f, "expected `true` or `false`"),
+            ErrorCode::ExpectedInteger => write!(f, "expected integer"),
+            ErrorCode::ExpectedFloat => write!(f, "expected number"),
+            ErrorCode::InvalidNumber => write!(f, "invalid number"),
+            ErrorCode::NumberOutOfRange(ty) => write!(f, "number out of range for `{ty}`"),
+            ErrorCode::FloatMustBeFinite => {
+                write!(f, "NaN and infinity cannot be represented in JSON")
+            }
+            ErrorCode::ExpectedChar => write!(f, "expected a string of exactly one character"),
+            ErrorCode::ExpectedString => write!(f, "expected string"),
+            ErrorCode::InvalidEscape => write!(f, "invalid escape sequence"),
+            ErrorCode::InvalidUnicodeCodePoint => write!(f, "invalid unicode code point"),
+            ErrorCode::ControlCharacterInString => {
+                write!(f, "control character in string must be escaped")
+            }
+            ErrorCode::ExpectedBytes => write!(f, "expected string or array of bytes"),
+            ErrorCode::InvalidBase64 => write!(f, "invalid base64 string"),
+            ErrorCode::ExpectedNull => write!(f, "expected `null`"),
+            ErrorCode::ExpectedArray => write!(f, "expected `[`"),
+            ErrorCode::ExpectedArrayComma => write!(f, "expected `,` or `]` in array"),
+            ErrorCode::ExpectedArrayEnd => write!(f, "expected `]`"),
+            ErrorCode::ExpectedMap => write!(f, "expected `{{`"),
+            ErrorCode::ExpectedMapComma => write!(f, "expected `,` or `}}` in object"),
+            ErrorCode::ExpectedMapColon => write!(f, "expected `:` after object key"),
+            ErrorCode::ExpectedMapEnd => write!(f, "expected `}}`"),
+            ErrorCode::ExpectedEnum => write!(f, "expected string or object for enum"),
+            ErrorCode::KeyMustBeAString => {
+                write!(f, "object key must be a string, integer, bool or char")
+            }
+            ErrorCode::TrailingCharacters => write!(f, "trailing characters"),
+            ErrorCode::RecursionLimitExceeded => write!(f, "recursion limit exceeded"),
+            ErrorCode::StringTooLong => write!(f, "string longer than the limit"),
+            ErrorCode::CollectionTooLarge => write!(f, "too many elements in array or object"),
+        }
+    }
+}
+
+// Renders as, for example:
+//
+//   expected `:` after object key, found '1' at line 2 column 9
+//     |
+//   2 |     "a" 1
+//     |         ^
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.err.code)?;
+        let Some(location) = &self.err.location else {
+            return Ok(());
+        };
+        if self.err.code.is_expectation() {
+            match location.found {
+                Some(ch) => write!(f, ", found {ch:?}")?,
+                None => write!(f, ", found end of input")?,
+            }
         }
+        let Position { line, column, .. } = location.position;
+        write!(f, " at line {line} column {column}")?;
+
+        let window = &location.snippet;
+        let skip = location.snippet_column - 1;
+        let prefix = if skip > 0 { "..." } else { "" };
+        let suffix = if location.truncated { "..." } else { "" };
+        // Tabs are kept in the padding so the caret lines up in a terminal.
+        let padding: String = prefix
+            .chars()
+            .chain(window.chars())
+            .take(prefix.len() + column - 1 - skip)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(line.to_string().len());
+        write!(
+            f,
+            "\n{gutter} |\n{line} | {prefix}{window}{suffix}\n{gutter} | {padding}^"
+        )
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.err.code {
+            ErrorCode::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_long_line() {
+    // Only the window around the fault is kept, however long the line.
+    let input = format!("[{}x{}]\r\n", "1,".repeat(50_000), ",1".repeat(50_000));
+    let offset = input.find('x').unwrap();
+    let err = Error::at(ErrorCode::ExpectedArrayComma, &input, offset);
+    let location = err.err.location.as_ref().unwrap();
+    assert_eq!(location.snippet.chars().count(), 2 * SNIPPET_CONTEXT);
+    assert_eq!(location.snippet_column, offset + 1 - SNIPPET_CONTEXT);
+    assert!(location.truncated);
+    let message = err.to_string();
+    let lines: Vec<&str> = message.lines().collect();
+    assert_eq!(
+        lines[2],
+        format!("1 | ...{}x{}...", "1,".repeat(20), ",1".repeat(19) + ",")
+    );
+    assert_eq!(lines[3], format!("  | {}^", " ".repeat(43)));
+
+    // Near the end of the line there is nothing more to show, the `\r`
+    // included.
+    let offset = input.len() - 3;
+    let err = Error::at(ErrorCode::ExpectedArrayComma, &input, offset);
+    let location = err.err.location.as_ref().unwrap();
+    assert!(!location.truncated);
+    assert!(location.snippet.ends_with(",1]"));
+}
@@ -1,6 +1,24 @@
+mod macros;
+
+mod bytes;
 mod de;
 mod error;
 mod ser;
+mod value;
+
+pub use bytes::BytesEncoding;
+pub use de::{
+    Deserializer, DeserializerOptions, Limits, ReaderStreamDeserializer, StreamDeserializer,
+    from_reader, from_slice, from_str, from_str_with_limits,
+};
+pub use error::{Category, Error, ErrorCode, Position, Result};
+pub use ser::{
+    CompactFormatter, Compound, Formatter, PrettyFormatter, Serializer, to_string,
+    to_string_canonical, to_string_pretty, to_vec, to_vec_canonical, to_vec_pretty, to_writer,
+    to_writer_canonical, to_writer_pretty,
+};
+pub use value::{Map, Number, Value, from_value, to_value};
 
-pub use error::{Error, Result};
-pub use ser::{Serializer, to_string};
+// Used by `json!`, not part of the API.
+#[doc(hidden)]
+pub use value::to_value_lossy as __to_value_lossy;
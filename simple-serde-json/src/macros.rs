@@ -0,0 +1,83 @@
+// Build a `Value` from JSON-like syntax:
+//
+//     let value = json!({
+//         "name": "John",
+//         "age": 30 + 13,
+//         "tags": ["a", "b"],
+//         "manager": null
+//     });
+//
+// Keys may be string literals or any expression in parentheses. Values may be
+// nested literals or any expression whose type implements `Serialize`. NaN and
+// infinity, which JSON cannot represent, come out as `null`.
+//
+// Arrays and objects are parsed one element at a time: `null`, arrays and
+// objects first, as they are not expressions to serialize, and anything else
+// as an expression up to the next top level comma, which is what allows
+// elements like `-1` or `a + b`.
+#[macro_export]
+macro_rules! json {
+    // Array with the elements parsed so far in `[$($elems,)*]`.
+    (@array [$($elems:expr,)*]) => {
+        $crate::Value::Array(vec![$($elems,)*])
+    };
+    (@array [$($elems:expr,)*] null $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elems,)* $crate::json!(null),] $($($rest)*)?)
+    };
+    (@array [$($elems:expr,)*] [$($array:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elems,)* $crate::json!([$($array)*]),] $($($rest)*)?)
+    };
+    (@array [$($elems:expr,)*] {$($object:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::json!(@array [$($elems,)* $crate::json!({$($object)*}),] $($($rest)*)?)
+    };
+    (@array [$($elems:expr,)*] $next:expr , $($rest:tt)*) => {
+        $crate::json!(@array [$($elems,)* $crate::json!($next),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $last:expr) => {
+        $crate::json!(@array [$($elems,)* $crate::json!($last),])
+    };
+
+    // Object entries are inserted into `$map` one at a time.
+    (@object $map:ident) => {};
+    (@object $map:ident $key:tt : null $(, $($rest:tt)*)?) => {
+        $crate::json!(@entry $map $key $crate::json!(null));
+        $crate::json!(@object $map $($($rest)*)?);
+    };
+    (@object $map:ident $key:tt : [$($array:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::json!(@entry $map $key $crate::json!([$($array)*]));
+        $crate::json!(@object $map $($($rest)*)?);
+    };
+    (@object $map:ident $key:tt : {$($object:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::json!(@entry $map $key $crate::json!({$($object)*}));
+        $crate::json!(@object $map $($($rest)*)?);
+    };
+    (@object $map:ident $key:tt : $value:expr , $($rest:tt)*) => {
+        $crate::json!(@entry $map $key $crate::json!($value));
+        $crate::json!(@object $map $($rest)*);
+    };
+    (@object $map:ident $key:tt : $value:expr) => {
+        $crate::json!(@entry $map $key $crate::json!($value));
+    };
+    (@entry $map:ident $key:tt $value:expr) => {
+        $map.insert(::std::string::ToString::to_string(&$key), $value);
+    };
+
+    (null) => {
+        $crate::Value::Null
+    };
+    ([ $($tt:tt)* ]) => {
+        $crate::json!(@array [] $($tt)*)
+    };
+    ({}) => {
+        $crate::Value::Object($crate::Map::new())
+    };
+    ({ $($tt:tt)+ }) => {{
+        let mut map = $crate::Map::new();
+        $crate::json!(@object map $($tt)+);
+        $crate::Value::Object(map)
+    }};
+    // Anything else is an expression to serialize.
+    ($other:expr) => {
+        $crate::__to_value_lossy(&$other)
+    };
+}
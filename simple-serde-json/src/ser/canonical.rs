@@ -0,0 +1,152 @@
+use super::formatter::CanonicalFormatter;
+use super::{Serializer, into_string};
+use crate::error::Result;
+use crate::value::{Value, to_value};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, ser};
+use std::io::{self, Write};
+
+// Serialize as canonical JSON in the style of RFC 8785, for hashing and
+// signing: the same data always gives the same bytes, whatever order a
+// `HashMap` happened to iterate in.
+//
+// - Object keys are sorted, struct fields included. Like RFC 8785 they are
+//   compared as UTF-16 code units, which only differs from comparing the
+//   `&str`s for characters outside the Basic Multilingual Plane.
+// - Floats are written as JavaScript would, the shortest text that reads back
+//   the same: `1.0` becomes `1`, `1e21` becomes `1e+21`.
+// - There is no whitespace and strings only have the escapes JSON requires.
+//
+// Integers are written exactly. RFC 8785 treats every number as an `f64`,
+// so integers beyond 2^53 have no canonical form there. An `i128` or `u128`
+// has to fit in 64 bits.
+//
+// Keys can only be sorted once they are all known, so the value is converted
+// into a `Value` first. Byte strings end up as arrays of numbers.
+pub fn to_writer_canonical<W: io::Write, T: ?Sized + Serialize>(
+    writer: W,
+    value: &T,
+) -> Result<()> {
+    let value = to_value(value)?;
+    let mut serializer = Serializer::with_formatter(io::BufWriter::new(writer), CanonicalFormatter);
+    Canonical(&value).serialize(&mut serializer)?;
+    serializer.into_inner().flush()?;
+    Ok(())
+}
+
+pub fn to_vec_canonical<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    let value = to_value(value)?;
+    let mut writer = Vec::with_capacity(128);
+    Canonical(&value).serialize(&mut Serializer::with_formatter(
+        &mut writer,
+        CanonicalFormatter,
+    ))?;
+    Ok(writer)
+}
+
+pub fn to_string_canonical<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    Ok(into_string(to_vec_canonical(value)?))
+}
+
+// Serializes a `Value` with the entries of every object in canonical order.
+struct Canonical<'a>(&'a Value);
+
+impl Serialize for Canonical<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for element in array {
+                    seq.serialize_element(&Canonical(element))?;
+                }
+                seq.end()
+            }
+            Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, &Canonical(value))?;
+                }
+                map.end()
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+#[test]
+fn test_canonical() {
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Payment {
+        to: &'static str,
+        amount: f64,
+        meta: HashMap<&'static str, f64>,
+    }
+
+    let payment = Payment {
+        to: "b\u{e9}b\n",
+        amount: 100.0,
+        meta: HashMap::from([("z", 1e21), ("a", 0.1), ("m", -1.5e-7), ("b", 1e-6)]),
+    };
+    assert_eq!(
+        to_string_canonical(&payment).unwrap(),
+        "{\"amount\":100,\"meta\":{\"a\":0.1,\"b\":0.000001,\"m\":-1.5e-7,\"z\":1e+21},\"to\":\"b\u{e9}b\\n\"}"
+    );
+
+    // Examples from RFC 8785, section 3.2.2.3 and appendix B.
+    let numbers = [
+        0.0,
+        -0.0,
+        333333333.3333333,
+        1e30,
+        4.5,
+        2e-3,
+        0.000000000000000000000000001,
+        5e-324,
+        -1.7976931348623157e308,
+        9007199254740992.0,
+        295147905179352830000.0,
+        1e21,
+        1e-7,
+    ];
+    assert_eq!(
+        to_string_canonical(&numbers).unwrap(),
+        "[0,0,333333333.3333333,1e+30,4.5,0.002,1e-27,5e-324,-1.7976931348623157e+308,\
+         9007199254740992,295147905179352830000,1e+21,1e-7]"
+    );
+    assert_eq!(to_string_canonical(&1.5f32).unwrap(), "1.5");
+
+    // Keys compare as UTF-16: U+1F600 is a surrogate pair starting with
+    // 0xD83D, which sorts before U+FB01.
+    let map = HashMap::from([("\u{fb01}", 1), ("\u{1f600}", 2), ("\r", 3), ("1", 4)]);
+    assert_eq!(
+        to_string_canonical(&map).unwrap(),
+        "{\"\\r\":3,\"1\":4,\"\u{1f600}\":2,\"\u{fb01}\":1}"
+    );
+
+    let mut writer = Vec::new();
+    to_writer_canonical(&mut writer, &[(); 2]).unwrap();
+    assert_eq!(writer, b"[null,null]");
+    assert!(to_string_canonical(&f64::NAN).is_err());
+
+    let wide = (
+        i128::from(i64::MIN),
+        u128::from(u64::MAX),
+        HashMap::from([(-1i128, 1u128)]),
+    );
+    assert_eq!(
+        to_string_canonical(&wide).unwrap(),
+        "[-9223372036854775808,18446744073709551615,{\"-1\":1}]"
+    );
+    assert!(matches!(
+        to_string_canonical(&u128::MAX).unwrap_err().code(),
+        crate::ErrorCode::NumberOutOfRange("u128")
+    ));
+    assert!(matches!(
+        to_string_canonical(&i128::MIN).unwrap_err().code(),
+        crate::ErrorCode::NumberOutOfRange("i128")
+    ));
+}
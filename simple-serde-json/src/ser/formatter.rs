@@ -0,0 +1,260 @@
+use std::io;
+
+// Decides how the punctuation between JSON tokens is laid out. The serializer
+// writes the values themselves and asks the formatter for everything around
+// them, so compact and pretty output share one `Serializer`. Floats are the
+// exception, they have more than one valid spelling and the formatter picks
+// one.
+//
+// Every method has a default that produces compact output.
+pub trait Formatter {
+    // The shortest text that parses back to the same float, always with a
+    // fraction or exponent so it reads back as a float: `1.0`, `0.1`,
+    // `1e100`. Only finite values get here.
+    fn write_f32<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f32) -> io::Result<()> {
+        write!(writer, "{value:?}")
+    }
+
+    fn write_f64<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        write!(writer, "{value:?}")
+    }
+
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"[")
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"]")
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b",")
+        }
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"{")
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"}")
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b",")
+        }
+    }
+
+    fn end_object_key<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b":")
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// No whitespace at all: `{"a":[1,2]}`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactFormatter;
+
+impl Formatter for CompactFormatter {}
+
+// One value per line, nested values indented by `indent`:
+//
+//     {
+//       "a": [
+//         1,
+//         2
+//       ]
+//     }
+//
+// Empty arrays and objects stay on one line as `[]` and `{}`.
+#[derive(Debug, Clone)]
+pub struct PrettyFormatter<'a> {
+    indent: &'a str,
+    current_indent: usize,
+    // Whether the array or object being closed had any values, which decides
+    // if the closing bracket goes on a line of its own.
+    has_value: bool,
+}
+
+impl<'a> PrettyFormatter<'a> {
+    // Indent with two spaces.
+    pub fn new() -> Self {
+        PrettyFormatter::with_indent("  ")
+    }
+
+    // Indent with the given string, for example `"\t"` or `"    "`.
+    pub fn with_indent(indent: &'a str) -> Self {
+        PrettyFormatter {
+            indent,
+            current_indent: 0,
+            has_value: false,
+        }
+    }
+
+    fn newline_and_indent<W: ?Sized + io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"\n")?;
+        for _ in 0..self.current_indent {
+            writer.write_all(self.indent.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn begin<W: ?Sized + io::Write>(&mut self, writer: &mut W, bracket: &[u8]) -> io::Result<()> {
+        self.current_indent += 1;
+        self.has_value = false;
+        writer.write_all(bracket)
+    }
+
+    fn end<W: ?Sized + io::Write>(&mut self, writer: &mut W, bracket: &[u8]) -> io::Result<()> {
+        self.current_indent -= 1;
+        if self.has_value {
+            self.newline_and_indent(writer)?;
+        }
+        writer.write_all(bracket)
+    }
+
+    fn begin_entry<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if !first {
+            writer.write_all(b",")?;
+        }
+        self.newline_and_indent(writer)
+    }
+}
+
+impl Default for PrettyFormatter<'_> {
+    fn default() -> Self {
+        PrettyFormatter::new()
+    }
+}
+
+impl Formatter for PrettyFormatter<'_> {
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(writer, b"[")
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end(writer, b"]")
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.begin_entry(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(writer, b"{")
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end(writer, b"}")
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.begin_entry(writer, first)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b": ")
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+}
+
+// The output of RFC 8785, the JSON Canonicalization Scheme: no whitespace and
+// floats spelled the way JavaScript's `Number.prototype.toString` does, so
+// `1.0` is `1` and `1e21` is `1e+21`. Sorting object keys is not something a
+// formatter can do, see `to_writer_canonical` for that.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CanonicalFormatter;
+
+impl CanonicalFormatter {
+    // `scientific` is the shortest round-trip text in Rust's `{:e}` format,
+    // like `-1.25e-7`, which has the digits JavaScript picks as well. Only
+    // where the decimal point goes and whether to use an exponent differ.
+    fn write_number<W: ?Sized + io::Write>(writer: &mut W, scientific: &str) -> io::Result<()> {
+        let (sign, scientific) = match scientific.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", scientific),
+        };
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((scientific, "0"));
+        let digits = mantissa.replace('.', "");
+        if digits == "0" {
+            // Negative zero as well.
+            return writer.write_all(b"0");
+        }
+        // The value is 0.DIGITS times ten to the `n`.
+        let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+        let k = digits.len() as i32;
+        match n {
+            _ if k <= n && n <= 21 => {
+                write!(writer, "{sign}{digits}{}", "0".repeat((n - k) as usize))
+            }
+            1..=21 => {
+                let (int, fraction) = digits.split_at(n as usize);
+                write!(writer, "{sign}{int}.{fraction}")
+            }
+            -5..=0 => write!(writer, "{sign}0.{}{digits}", "0".repeat(-n as usize)),
+            _ => {
+                let (first, rest) = digits.split_at(1);
+                let point = if rest.is_empty() { "" } else { "." };
+                let exp_sign = if n > 0 { "+" } else { "-" };
+                let exp = (n - 1).abs();
+                write!(writer, "{sign}{first}{point}{rest}e{exp_sign}{exp}")
+            }
+        }
+    }
+}
+
+impl Formatter for CanonicalFormatter {
+    fn write_f32<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f32) -> io::Result<()> {
+        CanonicalFormatter::write_number(writer, &format!("{value:e}"))
+    }
+
+    fn write_f64<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        CanonicalFormatter::write_number(writer, &format!("{value:e}"))
+    }
+}
@@ -0,0 +1,217 @@
+use super::{Formatter, Serializer};
+use crate::error::{Error, ErrorCode, Result};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::Display;
+use std::io;
+
+// Serializes the keys of a map. JSON object keys are always strings, so keys
+// that are strings already are kept as they are while integers, bools and
+// chars are turned into text: `{"1":"a","true":"b"}`. Anything that has no
+// obvious string form, like a sequence, a map or a float, is an error.
+//
+// What happens to the key is up to `K`, so that the text serializer and
+// `to_value` agree on which keys there are and how they are spelled.
+pub(crate) struct MapKeySerializer<K>(pub(crate) K);
+
+// Where `MapKeySerializer` puts a key.
+pub(crate) trait KeyOutput {
+    type Ok;
+
+    // A key that is a string already.
+    fn string(self, key: &str) -> Result<Self::Ok>;
+
+    // A key written as its `Display` form, which has no characters needing
+    // escapes.
+    fn display(self, key: impl Display) -> Result<Self::Ok>;
+}
+
+// Writes keys to the output of a `Serializer`, in quotes.
+pub(super) struct KeyWriter<'a, W, F> {
+    pub(super) ser: &'a mut Serializer<W, F>,
+}
+
+impl<W: io::Write, F: Formatter> KeyOutput for KeyWriter<'_, W, F> {
+    type Ok = ();
+
+    fn string(self, key: &str) -> Result<()> {
+        ser::Serializer::serialize_str(&mut *self.ser, key)
+    }
+
+    fn display(self, key: impl Display) -> Result<()> {
+        write!(self.ser.writer, "\"{key}\"")?;
+        Ok(())
+    }
+}
+
+fn key_must_be_a_string() -> Error {
+    Error::new(ErrorCode::KeyMustBeAString)
+}
+
+impl<K: KeyOutput> ser::Serializer for MapKeySerializer<K> {
+    type Ok = K::Ok;
+    type Error = Error;
+    type SerializeSeq = Impossible<K::Ok, Error>;
+    type SerializeTuple = Impossible<K::Ok, Error>;
+    type SerializeTupleStruct = Impossible<K::Ok, Error>;
+    type SerializeTupleVariant = Impossible<K::Ok, Error>;
+    type SerializeMap = Impossible<K::Ok, Error>;
+    type SerializeStruct = Impossible<K::Ok, Error>;
+    type SerializeStructVariant = Impossible<K::Ok, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<K::Ok> {
+        self.0.string(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<K::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<K::Ok> {
+        self.0.display(v)
+    }
+
+    // A fieldless enum is written as its variant name, as elsewhere.
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<K::Ok> {
+        self.0.string(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<K::Ok>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<K::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<K::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<K::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<K::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<K::Ok>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<K::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<K::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<K::Ok>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
+    }
+}
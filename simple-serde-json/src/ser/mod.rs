@@ -1,13 +1,117 @@
-use crate::error::{Error, Result};
+use crate::bytes::{self, BytesEncoding};
+use crate::error::{Error, ErrorCode, Result};
 use serde::{Serialize, ser};
+use std::io::{self, Write};
 
-pub struct Serializer {
-    output: String,
+mod canonical;
+mod formatter;
+mod key;
+
+pub use canonical::{to_string_canonical, to_vec_canonical, to_writer_canonical};
+pub use formatter::{CompactFormatter, Formatter, PrettyFormatter};
+use key::KeyWriter;
+pub(crate) use key::{KeyOutput, MapKeySerializer};
+
+// Writes JSON straight into `writer` as values are visited, so the document
+// never has to fit in memory. Writers that are expensive to call, like files
+// and sockets, should be wrapped in an `io::BufWriter`; `to_writer` does that.
+pub struct Serializer<W, F = CompactFormatter> {
+    writer: W,
+    formatter: F,
+    bytes: BytesEncoding,
+}
+
+impl<W: io::Write> Serializer<W> {
+    // A serializer producing compact JSON.
+    pub fn new(writer: W) -> Self {
+        Serializer::with_formatter(writer, CompactFormatter)
+    }
+}
+
+impl<'a, W: io::Write> Serializer<W, PrettyFormatter<'a>> {
+    // A serializer producing indented JSON, see `PrettyFormatter`.
+    pub fn pretty(writer: W) -> Self {
+        Serializer::with_formatter(writer, PrettyFormatter::new())
+    }
+}
+
+impl<W: io::Write, F: Formatter> Serializer<W, F> {
+    // A serializer laying out its output with a custom formatter, e.g.
+    // `PrettyFormatter::with_indent("\t")`.
+    pub fn with_formatter(writer: W, formatter: F) -> Self {
+        Serializer {
+            writer,
+            formatter,
+            bytes: BytesEncoding::default(),
+        }
+    }
+
+    // How byte strings are written, an array of numbers unless set otherwise.
+    // The deserializer has to be given the same encoding to read them back.
+    pub fn bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes = encoding;
+        self
+    }
+
+    // Give back the writer. Nothing is buffered by the serializer itself, so
+    // everything serialized so far has been handed to it.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    // Enum variants with data are externally tagged: `{"Variant":...}`. This
+    // writes everything up to the data.
+    fn begin_variant(&mut self, variant: &'static str) -> Result<()> {
+        self.formatter.begin_object(&mut self.writer)?;
+        self.formatter.begin_object_key(&mut self.writer, true)?;
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+        self.formatter.end_object_key(&mut self.writer)?;
+        self.formatter.begin_object_value(&mut self.writer)?;
+        Ok(())
+    }
+
+    // Close the object opened by `begin_variant`.
+    fn end_variant(&mut self) -> Result<()> {
+        self.formatter.end_object_value(&mut self.writer)?;
+        self.formatter.end_object(&mut self.writer)?;
+        Ok(())
+    }
+}
+
+// Whether a compound value still waits for its first element, which decides
+// if a separator has to be written before the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    First,
+    Rest,
+}
+
+// Returned from `Serializer::serialize_seq`, `serialize_map` and friends to
+// serialize the elements of a compound value.
+pub struct Compound<'a, W, F> {
+    ser: &'a mut Serializer<W, F>,
+    state: State,
+}
+
+impl<'a, W, F> Compound<'a, W, F> {
+    fn new(ser: &'a mut Serializer<W, F>) -> Self {
+        Compound {
+            ser,
+            state: State::First,
+        }
+    }
+
+    // Report whether this is the first element and move past it.
+    fn next_is_first(&mut self) -> bool {
+        let first = self.state == State::First;
+        self.state = State::Rest;
+        first
+    }
 }
 
 // This impl is SerializeSeq so these methods are called after `serialize_seq`
 // is called on the Serializer.
-impl ser::SerializeSeq for &mut Serializer {
+impl<W: io::Write, F: Formatter> ser::SerializeSeq for Compound<'_, W, F> {
     // Must match the `Ok` type of the serializer.
     type Ok = ();
     // Must match the `Error` type of the serializer.
@@ -18,68 +122,75 @@ impl ser::SerializeSeq for &mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        if !self.output.ends_with('[') {
-            self.output += ",";
-        }
-        value.serialize(&mut **self)
+        let first = self.next_is_first();
+        let ser = &mut *self.ser;
+        ser.formatter.begin_array_value(&mut ser.writer, first)?;
+        value.serialize(&mut *ser)?;
+        ser.formatter.end_array_value(&mut ser.writer)?;
+        Ok(())
     }
 
     // Close the sequence.
     fn end(self) -> Result<Self::Ok> {
-        self.output += "]";
+        self.ser.formatter.end_array(&mut self.ser.writer)?;
         Ok(())
     }
 }
 
-impl ser::SerializeTuple for &mut Serializer {
+// Tuples are arrays in JSON, so they are written exactly like sequences.
+impl<W: io::Write, F: Formatter> ser::SerializeTuple for Compound<'_, W, F> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        todo!()
+        ser::SerializeSeq::end(self)
     }
 }
 
-impl ser::SerializeTupleStruct for &mut Serializer {
+// Same thing but for tuple structs.
+impl<W: io::Write, F: Formatter> ser::SerializeTupleStruct for Compound<'_, W, F> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        todo!()
+        ser::SerializeSeq::end(self)
     }
 }
 
-impl ser::SerializeTupleVariant for &mut Serializer {
+// Tuple variants are represented in JSON as `{ NAME: [DATA...] }`. The array
+// holding the data is handled like any other sequence.
+impl<W: io::Write, F: Formatter> ser::SerializeTupleVariant for Compound<'_, W, F> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        todo!()
+        self.ser.formatter.end_array(&mut self.ser.writer)?;
+        self.ser.end_variant()
     }
 }
 
-impl ser::SerializeMap for &mut Serializer {
+impl<W: io::Write, F: Formatter> ser::SerializeMap for Compound<'_, W, F> {
     type Ok = ();
     type Error = Error;
 
@@ -87,29 +198,34 @@ impl ser::SerializeMap for &mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        if !self.output.ends_with('{') {
-            self.output += ",";
-        }
-        key.serialize(&mut **self)
+        let first = self.next_is_first();
+        let ser = &mut *self.ser;
+        ser.formatter.begin_object_key(&mut ser.writer, first)?;
+        key.serialize(MapKeySerializer(KeyWriter { ser: &mut *ser }))?;
+        ser.formatter.end_object_key(&mut ser.writer)?;
+        Ok(())
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        self.output += ":";
-        value.serialize(&mut **self)
+        let ser = &mut *self.ser;
+        ser.formatter.begin_object_value(&mut ser.writer)?;
+        value.serialize(&mut *ser)?;
+        ser.formatter.end_object_value(&mut ser.writer)?;
+        Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        self.output += "}";
+        self.ser.formatter.end_object(&mut self.ser.writer)?;
         Ok(())
     }
 }
 
 // Structs are like maps in which the keys are constrained to be compile-time
 // constant strings.
-impl ser::SerializeStruct for &mut Serializer {
+impl<W: io::Write, F: Formatter> ser::SerializeStruct for Compound<'_, W, F> {
     type Ok = ();
     type Error = Error;
 
@@ -117,49 +233,45 @@ impl ser::SerializeStruct for &mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        if !self.output.ends_with('{') {
-            self.output += ",";
-        }
-        key.serialize(&mut **self)?;
-        self.output += ":";
-        value.serialize(&mut **self)
+        ser::SerializeMap::serialize_entry(self, key, value)
     }
 
     fn end(self) -> Result<()> {
-        self.output += "}";
-        Ok(())
+        ser::SerializeMap::end(self)
     }
 }
 
-impl ser::SerializeStructVariant for &mut Serializer {
+// Struct variants are represented in JSON as `{ NAME: { K: V, ... } }`.
+impl<W: io::Write, F: Formatter> ser::SerializeStructVariant for Compound<'_, W, F> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        ser::SerializeMap::serialize_entry(self, key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        todo!()
+        self.ser.formatter.end_object(&mut self.ser.writer)?;
+        self.ser.end_variant()
     }
 }
 
-impl ser::Serializer for &mut Serializer {
+impl<'a, W: io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F> {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeSeq = Compound<'a, W, F>;
+    type SerializeTuple = Compound<'a, W, F>;
+    type SerializeTupleStruct = Compound<'a, W, F>;
+    type SerializeTupleVariant = Compound<'a, W, F>;
+    type SerializeMap = Compound<'a, W, F>;
+    type SerializeStruct = Compound<'a, W, F>;
+    type SerializeStructVariant = Compound<'a, W, F>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
-        self.output += if v { "true" } else { "false" };
+        self.writer.write_all(if v { b"true" } else { b"false" })?;
         Ok(())
     }
 
@@ -178,7 +290,12 @@ impl ser::Serializer for &mut Serializer {
     // Not particularly efficient but this is example code anyway. A more
     // performant approach would be to use the `itoa` crate.
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.output += &v.to_string();
+        write!(self.writer, "{v}")?;
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        write!(self.writer, "{v}")?;
         Ok(())
     }
 
@@ -195,114 +312,169 @@ impl ser::Serializer for &mut Serializer {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.output += &v.to_string();
+        write!(self.writer, "{v}")?;
         Ok(())
     }
 
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        write!(self.writer, "{v}")?;
+        Ok(())
+    }
+
+    // JSON has no NaN or infinity. The `Debug` format is used because it
+    // always writes a `.` or an exponent, so `1.0` comes back as a float, and
+    // switches to exponent notation for very large and very small values.
+    // `f32` is formatted as itself, widening it first would print digits that
+    // were never there (`0.1` as `0.10000000149011612`).
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
-        self.serialize_f64(f64::from(v))
+        if !v.is_finite() {
+            return Err(Error::new(ErrorCode::FloatMustBeFinite));
+        }
+        self.formatter.write_f32(&mut self.writer, v)?;
+        Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
-        self.output += &v.to_string();
+        if !v.is_finite() {
+            return Err(Error::new(ErrorCode::FloatMustBeFinite));
+        }
+        self.formatter.write_f64(&mut self.writer, v)?;
         Ok(())
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok> {
-        self.serialize_str(&v.to_string())
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
-    // This only works for strings that don't require escape sequences.
-    // For example, it would emit invalid JSON if the input string contains a '"' character.
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        self.output += "\"";
-        self.output += v;
-        self.output += "\"";
+        self.writer.write_all(b"\"")?;
+        escape_str(&mut self.writer, v)?;
+        self.writer.write_all(b"\"")?;
         Ok(())
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
-        todo!()
+    // JSON has no byte type, see `BytesEncoding` for the two ways to write
+    // them.
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        match self.bytes {
+            BytesEncoding::Array => {
+                use serde::ser::SerializeSeq;
+                let mut seq = self.serialize_seq(Some(v.len()))?;
+                for byte in v {
+                    seq.serialize_element(byte)?;
+                }
+                seq.end()
+            }
+            BytesEncoding::Base64 => self.serialize_str(&bytes::encode_base64(v)),
+        }
     }
 
+    // An absent optional is represented as the JSON `null`.
     fn serialize_none(self) -> Result<Self::Ok> {
-        todo!()
+        self.serialize_unit()
     }
 
-    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok>
+    // A present optional is represented as just the contained value. Note that
+    // this is a lossy representation. For example the values `Some(())` and
+    // `None` both serialize as just `null`. Unfortunately this is typically
+    // what people expect when working with JSON. Other formats are encouraged
+    // to behave more intelligently if possible.
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        value.serialize(self)
     }
 
+    // In Serde, unit means an anonymous value containing no data. Map this to
+    // JSON as `null`.
     fn serialize_unit(self) -> Result<Self::Ok> {
-        todo!()
+        self.writer.write_all(b"null")?;
+        Ok(())
     }
 
+    // Unit struct means a named value containing no data. Again, since there is
+    // no data, map this to JSON as `null`. There is no need to serialize the
+    // name in most formats.
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
-        todo!()
+        self.serialize_unit()
     }
 
+    // Unit variants are just the variant name as a string: `"Variant"`.
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok> {
-        todo!()
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<Self::Ok>
+    // As is done here, serializers are encouraged to treat newtype structs as
+    // insignificant wrappers around the data they contain.
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        value.serialize(self)
     }
 
+    // Newtype variants wrap their data in a single-entry object:
+    // `{"Variant":DATA}`.
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        self.begin_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.end_variant()
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.output += "[";
-        Ok(self)
+        self.formatter.begin_array(&mut self.writer)?;
+        Ok(Compound::new(self))
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        todo!()
+    // Tuples look just like sequences in JSON. Some formats may be able to
+    // represent tuples more efficiently by omitting the length, since tuple
+    // means that the corresponding `Deserialize` implementation will know the
+    // length without needing to look at the serialized data.
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
     }
 
+    // Tuple structs look just like sequences in JSON.
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        todo!()
+        self.serialize_seq(Some(len))
     }
 
+    // Tuple variants are `{"Variant":[DATA...]}`. The closing brackets are
+    // written by `SerializeTupleVariant::end`.
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        todo!()
+        self.begin_variant(variant)?;
+        self.formatter.begin_array(&mut self.writer)?;
+        Ok(Compound::new(self))
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.output += "{";
-        Ok(self)
+        self.formatter.begin_object(&mut self.writer)?;
+        Ok(Compound::new(self))
     }
 
     // Structs look just like maps in JSON. In particular, JSON requires that we
@@ -314,23 +486,93 @@ impl ser::Serializer for &mut Serializer {
         self.serialize_map(Some(len))
     }
 
+    // Struct variants are `{"Variant":{K:V,...}}`.
     fn serialize_struct_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        todo!()
+        self.begin_variant(variant)?;
+        self.formatter.begin_object(&mut self.writer)?;
+        Ok(Compound::new(self))
     }
 }
 
-pub fn to_string<T: Serialize>(value: &T) -> Result<String> {
-    let mut serializer = Serializer {
-        output: String::new(),
-    };
+// Write `v` with the escaping RFC 8259 requires: quotation mark, reverse solidus
+// and the control characters U+0000 to U+001F. Everything else, including
+// non-ASCII text, is copied through unchanged. Unescaped runs are written in
+// one go rather than character by character.
+fn escape_str<W: ?Sized + io::Write>(writer: &mut W, v: &str) -> io::Result<()> {
+    let mut start = 0;
+    for (i, ch) in v.char_indices() {
+        let escape: &[u8] = match ch {
+            '"' => b"\\\"",
+            '\\' => b"\\\\",
+            '\n' => b"\\n",
+            '\r' => b"\\r",
+            '\t' => b"\\t",
+            '\u{8}' => b"\\b",
+            '\u{c}' => b"\\f",
+            '\u{0}'..='\u{1F}' => {
+                writer.write_all(&v.as_bytes()[start..i])?;
+                write!(writer, "\\u{:04x}", ch as u32)?;
+                start = i + 1;
+                continue;
+            }
+            _ => continue,
+        };
+        // Every escaped character is ASCII, so it is exactly one byte long.
+        writer.write_all(&v.as_bytes()[start..i])?;
+        writer.write_all(escape)?;
+        start = i + 1;
+    }
+    writer.write_all(&v.as_bytes()[start..])
+}
+
+// Serialize into any writer. The writer is buffered internally and flushed
+// before returning, so passing a `File` or `TcpStream` directly is fine.
+pub fn to_writer<W: io::Write, T: ?Sized + Serialize>(writer: W, value: &T) -> Result<()> {
+    let mut serializer = Serializer::new(io::BufWriter::new(writer));
     value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    serializer.into_inner().flush()?;
+    Ok(())
+}
+
+// Like `to_writer` but indented with two spaces, one value per line.
+pub fn to_writer_pretty<W: io::Write, T: ?Sized + Serialize>(writer: W, value: &T) -> Result<()> {
+    let mut serializer = Serializer::pretty(io::BufWriter::new(writer));
+    value.serialize(&mut serializer)?;
+    serializer.into_inner().flush()?;
+    Ok(())
+}
+
+// A `Vec` is already an in-memory buffer, so it is written to directly.
+pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut writer = Vec::with_capacity(128);
+    value.serialize(&mut Serializer::new(&mut writer))?;
+    Ok(writer)
+}
+
+pub fn to_vec_pretty<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut writer = Vec::with_capacity(128);
+    value.serialize(&mut Serializer::pretty(&mut writer))?;
+    Ok(writer)
+}
+
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    Ok(into_string(to_vec(value)?))
+}
+
+// Like `to_string` but indented with two spaces, one value per line.
+pub fn to_string_pretty<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    Ok(into_string(to_vec_pretty(value)?))
+}
+
+// Everything the serializer writes comes from `&str`s or ASCII punctuation.
+fn into_string(vec: Vec<u8>) -> String {
+    String::from_utf8(vec).expect("serializer output is valid UTF-8")
 }
 
 #[test]
@@ -364,4 +606,455 @@ fn test_struct() {
         to_string(&test).unwrap(),
         r#"{"boolean":true,"ch":"a","int":1,"uint":2,"double":3.5,"string":"test","vec":["a","b"],"map":{"key1":"val1","key2":"val2"}}"#
     );
+
+    // 128-bit integers are written out in full and read back exactly.
+    let ints = (i128::MIN, u128::MAX);
+    let json = to_string(&ints).unwrap();
+    assert_eq!(
+        json,
+        "[-170141183460469231731687303715884105728,340282366920938463463374607431768211455]"
+    );
+    assert_eq!(crate::from_str::<(i128, u128)>(&json).unwrap(), ints);
+}
+
+#[test]
+fn test_option_unit_newtype_and_tuples() {
+    #[derive(Serialize)]
+    struct Unit;
+
+    #[derive(Serialize)]
+    struct Meters(u32);
+
+    #[derive(Serialize)]
+    struct Point(i32, i32);
+
+    #[derive(Serialize)]
+    struct Test {
+        none: Option<u32>,
+        some: Option<u32>,
+        unit: (),
+        unit_struct: Unit,
+        newtype: Meters,
+        tuple: (u8, &'static str, bool),
+        tuple_struct: Point,
+        nested: Option<Option<Meters>>,
+    }
+
+    let test = Test {
+        none: None,
+        some: Some(1),
+        unit: (),
+        unit_struct: Unit,
+        newtype: Meters(3),
+        tuple: (1, "a", false),
+        tuple_struct: Point(1, -2),
+        nested: Some(Some(Meters(4))),
+    };
+    assert_eq!(
+        to_string(&test).unwrap(),
+        r#"{"none":null,"some":1,"unit":null,"unit_struct":null,"newtype":3,"tuple":[1,"a",false],"tuple_struct":[1,-2],"nested":4}"#
+    );
+    assert_eq!(to_string_pretty(&Point(1, 2)).unwrap(), "[\n  1,\n  2\n]");
+}
+
+#[test]
+fn test_skip_and_flatten() {
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Page {
+        offset: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        limit: Option<u32>,
+    }
+
+    #[derive(Serialize)]
+    struct Request {
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        user: Option<&'static str>,
+        #[serde(flatten)]
+        page: Page,
+        #[serde(flatten)]
+        extra: BTreeMap<&'static str, u32>,
+    }
+
+    let request = Request {
+        tags: vec![],
+        user: None,
+        page: Page {
+            offset: 10,
+            limit: None,
+        },
+        extra: BTreeMap::new(),
+    };
+    assert_eq!(to_string(&request).unwrap(), r#"{"offset":10}"#);
+
+    let request = Request {
+        tags: vec!["a"],
+        user: Some("me"),
+        page: Page {
+            offset: 0,
+            limit: Some(5),
+        },
+        extra: BTreeMap::from([("x", 1)]),
+    };
+    let json = r#"{"tags":["a"],"user":"me","offset":0,"limit":5,"x":1}"#;
+    assert_eq!(to_string(&request).unwrap(), json);
+    assert_eq!(
+        crate::to_value(&request).unwrap(),
+        crate::from_str::<crate::Value>(json).unwrap()
+    );
+}
+
+#[test]
+fn test_string_escapes() {
+    assert_eq!(
+        to_string(&"a\"b\\c/d\u{8}\u{c}\n\r\t").unwrap(),
+        r#""a\"b\\c/d\b\f\n\r\t""#
+    );
+    assert_eq!(to_string(&"\u{0}\u{1f}").unwrap(), r#""\u0000\u001f""#);
+    assert_eq!(to_string(&"é中😀").unwrap(), "\"é中😀\"");
+    assert_eq!(to_string(&'"').unwrap(), r#""\"""#);
+}
+
+#[test]
+fn test_floats() {
+    use crate::ErrorCode;
+
+    assert_eq!(to_string(&1.0).unwrap(), "1.0");
+    assert_eq!(to_string(&-0.5).unwrap(), "-0.5");
+    assert_eq!(to_string(&0.1f32).unwrap(), "0.1");
+    assert_eq!(to_string(&1e300).unwrap(), "1e300");
+    assert_eq!(
+        to_string(&f64::MIN_POSITIVE).unwrap(),
+        "2.2250738585072014e-308"
+    );
+    assert_eq!(
+        crate::from_str::<f64>(&to_string(&1e300).unwrap()).unwrap(),
+        1e300
+    );
+
+    for float in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let err = to_string(&float).unwrap_err();
+        assert!(matches!(err.code(), ErrorCode::FloatMustBeFinite));
+        assert!(crate::to_value(&float).is_err());
+    }
+    assert!(to_string(&vec![1.0, f32::NAN as f64]).is_err());
+    assert!(to_string(&f32::INFINITY).is_err());
+}
+
+#[test]
+fn test_pretty() {
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Test {
+        int: u32,
+        vec: Vec<Vec<u32>>,
+        empty: Vec<u32>,
+        map: BTreeMap<&'static str, BTreeMap<&'static str, bool>>,
+    }
+
+    let test = Test {
+        int: 1,
+        vec: vec![vec![1, 2], vec![]],
+        empty: vec![],
+        map: BTreeMap::from([("a", BTreeMap::from([("b", true)])), ("c", BTreeMap::new())]),
+    };
+
+    let expected = r#"{
+  "int": 1,
+  "vec": [
+    [
+      1,
+      2
+    ],
+    []
+  ],
+  "empty": [],
+  "map": {
+    "a": {
+      "b": true
+    },
+    "c": {}
+  }
+}"#;
+    assert_eq!(to_string_pretty(&test).unwrap(), expected);
+
+    let mut serializer = Serializer::with_formatter(Vec::new(), PrettyFormatter::with_indent("\t"));
+    vec![1, 2].serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), b"[\n\t1,\n\t2\n]");
+}
+
+#[test]
+fn test_writer() {
+    use std::collections::BTreeMap;
+
+    // A writer that only accepts a few bytes per call, to make sure nothing
+    // relies on a value being written in one piece.
+    struct Trickle(Vec<u8>);
+
+    impl io::Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let value = BTreeMap::from([("a", vec!["x[", "{y"]), ("b", vec![])]);
+    let expected = r#"{"a":["x[","{y"],"b":[]}"#;
+
+    let mut trickle = Trickle(Vec::new());
+    to_writer(&mut trickle, &value).unwrap();
+    assert_eq!(trickle.0, expected.as_bytes());
+    assert_eq!(to_vec(&value).unwrap(), expected.as_bytes());
+    assert_eq!(to_string(&value).unwrap(), expected);
+
+    let mut pretty = Vec::new();
+    to_writer_pretty(&mut pretty, &vec![1]).unwrap();
+    assert_eq!(pretty, b"[\n  1\n]");
+
+    struct Broken;
+
+    impl io::Write for Broken {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    assert!(to_writer(Broken, &value).unwrap_err().is_io());
+}
+
+#[test]
+fn test_enum() {
+    #[derive(Serialize)]
+    enum E {
+        Unit,
+        Newtype(u32),
+        Tuple(u32, u32),
+        Struct { a: u32 },
+    }
+
+    let u = E::Unit;
+    let expected = r#""Unit""#;
+    assert_eq!(to_string(&u).unwrap(), expected);
+
+    let n = E::Newtype(1);
+    let expected = r#"{"Newtype":1}"#;
+    assert_eq!(to_string(&n).unwrap(), expected);
+
+    let t = E::Tuple(1, 2);
+    let expected = r#"{"Tuple":[1,2]}"#;
+    assert_eq!(to_string(&t).unwrap(), expected);
+
+    let s = E::Struct { a: 1 };
+    let expected = r#"{"Struct":{"a":1}}"#;
+    assert_eq!(to_string(&s).unwrap(), expected);
+
+    let expected = "{\n  \"Struct\": {\n    \"a\": 1\n  }\n}";
+    assert_eq!(to_string_pretty(&s).unwrap(), expected);
+
+    let expected = "{\n  \"Tuple\": [\n    1,\n    2\n  ]\n}";
+    assert_eq!(to_string_pretty(&t).unwrap(), expected);
+}
+
+#[test]
+fn test_enum_representations() {
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Unit,
+        Struct { a: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Unit,
+        Newtype(u32),
+        Tuple(u32, u32),
+        Struct { a: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Struct { a: u32 },
+        Tuple(u32, u32),
+        Newtype(String),
+    }
+
+    fn round_trip<'de, T>(value: T, expected: &'de str)
+    where
+        T: Serialize + Deserialize<'de> + PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(to_string(&value).unwrap(), expected);
+        assert_eq!(crate::from_str::<T>(expected).unwrap(), value);
+    }
+
+    round_trip(Internal::Unit, r#"{"type":"Unit"}"#);
+    round_trip(Internal::Struct { a: 1 }, r#"{"type":"Struct","a":1}"#);
+
+    round_trip(Adjacent::Unit, r#"{"t":"Unit"}"#);
+    round_trip(Adjacent::Newtype(1), r#"{"t":"Newtype","c":1}"#);
+    round_trip(Adjacent::Tuple(1, 2), r#"{"t":"Tuple","c":[1,2]}"#);
+    round_trip(Adjacent::Struct { a: 1 }, r#"{"t":"Struct","c":{"a":1}}"#);
+
+    round_trip(Untagged::Struct { a: 1 }, r#"{"a":1}"#);
+    round_trip(Untagged::Tuple(1, 2), "[1,2]");
+    round_trip(Untagged::Newtype("x".to_owned()), r#""x""#);
+}
+
+#[test]
+fn test_map_keys() {
+    use serde::Serialize;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    #[derive(Serialize, PartialEq, Eq, Hash)]
+    struct Id(u32);
+
+    let map = BTreeMap::from([(-1, "a"), (2, "b")]);
+    assert_eq!(to_string(&map).unwrap(), r#"{"-1":"a","2":"b"}"#);
+    let map = BTreeMap::from([(i128::MIN, 0), (1, 1)]);
+    let json = to_string(&map).unwrap();
+    assert_eq!(
+        json,
+        r#"{"-170141183460469231731687303715884105728":0,"1":1}"#
+    );
+    assert_eq!(crate::from_str::<BTreeMap<i128, u8>>(&json).unwrap(), map);
+    let map = BTreeMap::from([(u128::MAX, 0)]);
+    let json = to_string(&map).unwrap();
+    assert_eq!(crate::from_str::<BTreeMap<u128, u8>>(&json).unwrap(), map);
+    let map = BTreeMap::from([(false, 0), (true, 1)]);
+    assert_eq!(to_string(&map).unwrap(), r#"{"false":0,"true":1}"#);
+    let map = BTreeMap::from([('"', 0), ('x', 1)]);
+    assert_eq!(to_string(&map).unwrap(), r#"{"\"":0,"x":1}"#);
+    let map = BTreeMap::from([(Color::Red, 0), (Color::Green, 1)]);
+    assert_eq!(to_string(&map).unwrap(), r#"{"Red":0,"Green":1}"#);
+    let map = HashMap::from([(Id(7), 0)]);
+    assert_eq!(to_string(&map).unwrap(), r#"{"7":0}"#);
+    assert_eq!(
+        to_string_pretty(&BTreeMap::from([(1u8, [2])])).unwrap(),
+        "{\n  \"1\": [\n    2\n  ]\n}"
+    );
+
+    // Keys without an obvious string form are rejected.
+    let err = to_string(&BTreeMap::from([(vec![1], 0)])).unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::KeyMustBeAString));
+    assert_eq!(
+        err.to_string(),
+        "object key must be a string, integer, bool or char"
+    );
+    assert!(to_string(&HashMap::from([(Some(1), 0)])).is_err());
+    assert!(to_string(&HashMap::from([((), 0)])).is_err());
+    assert!(crate::to_value(&BTreeMap::from([(vec![1], 0)])).is_err());
+}
+
+#[test]
+fn test_bytes() {
+    use crate::{DeserializerOptions, Value};
+    use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+    use std::fmt;
+
+    // What `serde_bytes` does: serialize with `serialize_bytes` and accept
+    // any of the forms a deserializer may hand bytes out in.
+    #[derive(PartialEq, Debug)]
+    struct Blob(Vec<u8>);
+
+    impl Serialize for Blob {
+        fn serialize<S: ser::Serializer>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Blob {
+        fn deserialize<D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> std::result::Result<Self, D::Error> {
+            struct BlobVisitor;
+
+            impl<'de> Visitor<'de> for BlobVisitor {
+                type Value = Blob;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("bytes")
+                }
+
+                fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Blob, E> {
+                    Ok(Blob(v.to_vec()))
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(
+                    self,
+                    mut seq: A,
+                ) -> std::result::Result<Blob, A::Error> {
+                    let mut bytes = Vec::new();
+                    while let Some(byte) = seq.next_element()? {
+                        bytes.push(byte);
+                    }
+                    Ok(Blob(bytes))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BlobVisitor)
+        }
+    }
+
+    let blob = Blob(b"hi\xff".to_vec());
+    assert_eq!(to_string(&blob).unwrap(), "[104,105,255]");
+    assert_eq!(crate::from_str::<Blob>("[104,105,255]").unwrap(), blob);
+    assert_eq!(
+        crate::to_value(&blob).unwrap(),
+        crate::json!([104, 105, 255])
+    );
+    assert_eq!(
+        crate::from_str::<Blob>(r#""hi""#).unwrap(),
+        Blob(b"hi".to_vec())
+    );
+
+    let mut serializer = Serializer::new(Vec::new()).bytes_encoding(BytesEncoding::Base64);
+    (&blob, Blob(Vec::new()))
+        .serialize(&mut serializer)
+        .unwrap();
+    let json = String::from_utf8(serializer.into_inner()).unwrap();
+    assert_eq!(json, r#"["aGn/",""]"#);
+
+    let options = DeserializerOptions::new().bytes_encoding(BytesEncoding::Base64);
+    assert_eq!(
+        options.from_str::<(Blob, Blob)>(&json).unwrap(),
+        (blob, Blob(Vec::new()))
+    );
+    let err = options
+        .from_str::<Vec<Blob>>(r#"["aGk=", "aGk"]"#)
+        .unwrap_err();
+    assert!(matches!(err.code(), ErrorCode::InvalidBase64));
+    assert_eq!(err.column(), Some(10));
+    assert!(options.from_str::<Blob>("[104]").is_err());
+    // Other strings are not affected.
+    assert_eq!(
+        options.from_str::<Value>(r#""aGk=""#).unwrap(),
+        crate::json!("aGk=")
+    );
 }
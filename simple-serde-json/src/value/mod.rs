@@ -0,0 +1,562 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::ops;
+
+mod de;
+mod ser;
+
+pub use de::from_value;
+pub use ser::{to_value, to_value_lossy};
+
+// Objects keep their keys sorted, which makes `Value` output deterministic.
+pub type Map = BTreeMap<String, Value>;
+
+// Any JSON document, for when its shape is not known at compile time.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Map),
+}
+
+// A JSON number. Integers are kept exact instead of going through `f64`, so a
+// `u64` id survives a round trip through `Value`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number {
+    n: N,
+}
+
+// Non-negative integers are always `PosInt` so that equal numbers compare
+// equal no matter how they were built.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum N {
+    PosInt(u64),
+    NegInt(i64),
+    Float(f64),
+}
+
+impl Number {
+    // `None` for NaN and infinities, which JSON cannot represent.
+    pub fn from_f64(f: f64) -> Option<Number> {
+        f.is_finite().then_some(Number { n: N::Float(f) })
+    }
+
+    pub fn is_u64(&self) -> bool {
+        matches!(self.n, N::PosInt(_))
+    }
+
+    pub fn is_i64(&self) -> bool {
+        match self.n {
+            N::PosInt(u) => u <= i64::MAX as u64,
+            N::NegInt(_) => true,
+            N::Float(_) => false,
+        }
+    }
+
+    pub fn is_f64(&self) -> bool {
+        matches!(self.n, N::Float(_))
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.n {
+            N::PosInt(u) => Some(u),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.n {
+            N::PosInt(u) => i64::try_from(u).ok(),
+            N::NegInt(i) => Some(i),
+            N::Float(_) => None,
+        }
+    }
+
+    // Integers are converted, possibly losing precision above 2^53.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.n {
+            N::PosInt(u) => Some(u as f64),
+            N::NegInt(i) => Some(i as f64),
+            N::Float(f) => Some(f),
+        }
+    }
+}
+
+impl From<u64> for Number {
+    fn from(u: u64) -> Self {
+        Number { n: N::PosInt(u) }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Self {
+        match u64::try_from(i) {
+            Ok(u) => Number { n: N::PosInt(u) },
+            Err(_) => Number { n: N::NegInt(i) },
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.n {
+            N::PosInt(u) => write!(f, "{u}"),
+            N::NegInt(i) => write!(f, "{i}"),
+            N::Float(float) => write!(f, "{float}"),
+        }
+    }
+}
+
+impl Value {
+    // Look up a value by object key or array position. Returns `None` when
+    // the key or index does not exist or `self` is not of the right kind.
+    pub fn get<I: Index>(&self, index: I) -> Option<&Value> {
+        index.index_into(self)
+    }
+
+    pub fn get_mut<I: Index>(&mut self, index: I) -> Option<&mut Value> {
+        index.index_into_mut(self)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(Number::as_u64)
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(Number::as_i64)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().and_then(Number::as_f64)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Map> {
+        match self {
+            Value::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut Map> {
+        match self {
+            Value::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+// Things a `Value` can be indexed with: `usize` for arrays, strings for
+// objects. Sealed, so that `value["key"]` and `value[0]` stay unambiguous.
+pub trait Index: private::Sealed {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value>;
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value>;
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for usize {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl<T: ?Sized + Sealed> Sealed for &T {}
+}
+
+impl Index for usize {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        v.as_array().and_then(|array| array.get(*self))
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        v.as_array_mut().and_then(|array| array.get_mut(*self))
+    }
+}
+
+impl Index for str {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        v.as_object().and_then(|map| map.get(self))
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        v.as_object_mut().and_then(|map| map.get_mut(self))
+    }
+}
+
+impl Index for String {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        self.as_str().index_into(v)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().index_into_mut(v)
+    }
+}
+
+impl<T: ?Sized + Index> Index for &T {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        (**self).index_into(v)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).index_into_mut(v)
+    }
+}
+
+// A missing key or index reads as `Null` rather than panicking, so lookups
+// can be chained: `value["users"][3]["email"]`.
+impl<I: Index> ops::Index<I> for Value {
+    type Output = Value;
+
+    fn index(&self, index: I) -> &Value {
+        static NULL: Value = Value::Null;
+        index.index_into(self).unwrap_or(&NULL)
+    }
+}
+
+// Writing needs the slot to exist, so this panics on a missing key or index.
+impl<I: Index> ops::IndexMut<I> for Value {
+    fn index_mut(&mut self, index: I) -> &mut Value {
+        index
+            .index_into_mut(self)
+            .expect("cannot index into value: missing key, index out of bounds or wrong type")
+    }
+}
+
+// `{}` prints compact JSON, `{:#}` pretty-prints it.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = if f.alternate() {
+            crate::to_string_pretty(self)
+        } else {
+            crate::to_string(self)
+        };
+        f.write_str(&s.map_err(|_| fmt::Error)?)
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty => $wide:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(n: $ty) -> Self {
+                    Value::Number(Number::from(<$wide>::from(n)))
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(
+    u8 => u64, u16 => u64, u32 => u64, u64 => u64,
+    i8 => i64, i16 => i64, i32 => i64, i64 => i64
+);
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+// Non-finite floats have no JSON representation and become `Null`.
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Number::from_f64(f).map_or(Value::Null, Value::Number)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(f: f32) -> Self {
+        Value::from(f64::from(f))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Self {
+        Value::Array(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Map> for Value {
+    fn from(map: Map) -> Self {
+        Value::Object(map)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        option.map_or(Value::Null, Into::into)
+    }
+}
+
+#[test]
+fn test_parse_and_index() {
+    let value: Value = crate::from_str(
+        r#"{"users":[{"name":"a","age":30},{"name":"b","age":-1,"score":2.5}],"ok":true,"none":null}"#,
+    )
+    .unwrap();
+
+    assert_eq!(value["users"][0]["name"], Value::from("a"));
+    assert_eq!(value["users"][0]["age"].as_u64(), Some(30));
+    assert_eq!(value["users"][1]["age"].as_i64(), Some(-1));
+    assert_eq!(value["users"][1]["score"].as_f64(), Some(2.5));
+    assert_eq!(value["ok"].as_bool(), Some(true));
+    assert!(value["none"].is_null());
+    assert!(value["missing"]["deeper"][7].is_null());
+    assert_eq!(
+        value
+            .get("users")
+            .and_then(|u| u.get(1))
+            .map(|u| &u["name"]),
+        Some(&Value::from("b"))
+    );
+    assert_eq!(value.get(0), None);
+}
+
+#[test]
+fn test_map_keys() {
+    use std::collections::BTreeMap;
+
+    let map = BTreeMap::from([(1u32, true), (20, false)]);
+    let value = crate::to_value(&map).unwrap();
+    assert_eq!(value, crate::json!({ "1": true, "20": false }));
+    assert_eq!(from_value::<BTreeMap<u32, bool>>(value).unwrap(), map);
+
+    let value = crate::json!({ "true": 1 });
+    assert_eq!(
+        from_value::<BTreeMap<bool, u8>>(value.clone()).unwrap(),
+        BTreeMap::from([(true, 1)])
+    );
+    assert!(from_value::<BTreeMap<u8, u8>>(value).is_err());
+
+    // Integer keys have one way to be written, so `"1"` and `"01"` can't
+    // both become the key 1.
+    for key in ["+1", "01", " 1", "1.0", ""] {
+        let value = crate::json!({ "1": true, (key): false });
+        assert!(
+            matches!(
+                from_value::<BTreeMap<i32, bool>>(value).unwrap_err().code(),
+                crate::ErrorCode::ExpectedInteger
+            ),
+            "{key}"
+        );
+    }
+    assert!(matches!(
+        from_value::<BTreeMap<u8, bool>>(crate::json!({ "256": true }))
+            .unwrap_err()
+            .code(),
+        crate::ErrorCode::NumberOutOfRange("u8")
+    ));
+
+    let map = BTreeMap::from([(i128::MIN, 1), (-1, 2), (0, 3)]);
+    let value = crate::to_value(&map).unwrap();
+    assert_eq!(from_value::<BTreeMap<i128, u8>>(value).unwrap(), map);
+    let map = BTreeMap::from([(u128::MAX, 1)]);
+    let value = crate::to_value(&map).unwrap();
+    assert_eq!(from_value::<BTreeMap<u128, u8>>(value).unwrap(), map);
+}
+
+#[test]
+fn test_round_trip_through_text() {
+    let json = r#"{"a":[1,-2,3.5,"x",null,true],"b":{}}"#;
+    let value: Value = crate::from_str(json).unwrap();
+    assert_eq!(value.to_string(), json);
+    assert_eq!(
+        format!("{value:#}"),
+        "{\n  \"a\": [\n    1,\n    -2,\n    3.5,\n    \"x\",\n    null,\n    true\n  ],\n  \"b\": {}\n}"
+    );
+}
+
+#[test]
+fn test_typed_conversions() {
+    use crate::{ErrorCode, json};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Admin,
+        Guest(u32),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: Option<u8>,
+        kinds: Vec<Kind>,
+    }
+
+    let user = User {
+        name: "a".to_string(),
+        age: None,
+        kinds: vec![Kind::Admin, Kind::Guest(7)],
+    };
+    let value = to_value(&user).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "name": "a",
+            "age": null,
+            "kinds": ["Admin", {"Guest": 7}]
+        })
+    );
+    assert_eq!(from_value::<User>(value).unwrap(), user);
+
+    let err = from_value::<User>(json!({"name": 1})).unwrap_err();
+    assert!(err.is_data());
+
+    // Only as far as `json!`, which has nowhere to report the error.
+    assert!(to_value(&f64::NAN).is_err());
+
+    // 128-bit integers convert as long as a `Number` can hold them.
+    let n = -5i128;
+    assert_eq!(json!({"n": n}), json!({"n": -5}));
+    assert_eq!(to_value(&1u128).unwrap(), Value::from(1));
+    assert_eq!(
+        to_value(&u128::from(u64::MAX)).unwrap(),
+        Value::from(u64::MAX)
+    );
+    assert_eq!(
+        to_value(&i128::from(i64::MIN)).unwrap(),
+        Value::from(i64::MIN)
+    );
+    assert!(matches!(
+        to_value(&(u128::from(u64::MAX) + 1)).unwrap_err().code(),
+        ErrorCode::NumberOutOfRange("u128")
+    ));
+    assert!(matches!(
+        to_value(&(i128::from(i64::MIN) - 1)).unwrap_err().code(),
+        ErrorCode::NumberOutOfRange("i128")
+    ));
+}
+
+#[test]
+fn test_json_macro() {
+    use crate::json;
+
+    let name = "key";
+    let n = 40;
+    let mut value = json!({
+        "null": null,
+        "neg": -1,
+        "sum": n + 2,
+        "nested": [1, [true, "two"], {"x": 3.5},],
+        (name): [],
+    });
+    assert!(value["null"].is_null());
+    assert_eq!(value["neg"].as_i64(), Some(-1));
+    assert_eq!(value["sum"].as_u64(), Some(42));
+    assert_eq!(value["nested"][1][1].as_str(), Some("two"));
+    assert_eq!(value["nested"][2]["x"].as_f64(), Some(3.5));
+    assert_eq!(value["key"], json!([]));
+
+    value["neg"] = json!("changed");
+    assert_eq!(value["neg"], Value::from("changed"));
+    assert_eq!(json!({}), Value::Object(Map::new()));
+
+    // JSON has no NaN or infinity, they become `null` wherever they are.
+    assert!(json!(f64::NAN).is_null());
+    assert_eq!(
+        json!([f64::INFINITY, 1.5, f32::NEG_INFINITY]),
+        json!([null, 1.5, null])
+    );
+    assert_eq!(
+        json!({"point": (0.5, f64::NAN)}),
+        json!({"point": [0.5, null]})
+    );
+}
+
+#[test]
+fn test_json_macro_long() {
+    use crate::json;
+
+    // Elements are parsed one at a time, so the recursion limit is only
+    // reached far beyond this.
+    let array = json!([
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+        25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47,
+        48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70,
+        71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93,
+        94, 95, 96, 97, 98, 99
+    ]);
+    assert_eq!(array, Value::Array((0..100).map(Value::from).collect()));
+
+    let object = json!({
+        "k0": 0, "k1": 1, "k2": 2, "k3": 3, "k4": 4,
+        "k5": 5, "k6": 6, "k7": 7, "k8": 8, "k9": 9,
+        "k10": 10, "k11": 11, "k12": 12, "k13": 13, "k14": 14,
+        "k15": 15, "k16": 16, "k17": 17, "k18": 18, "k19": 19,
+        "k20": 20, "k21": 21, "k22": 22, "k23": 23, "k24": 24,
+        "k25": 25, "k26": 26, "k27": 27, "k28": 28, "k29": 29,
+        "k30": 30, "k31": 31, "k32": 32, "k33": 33, "k34": 34,
+        "k35": 35, "k36": 36, "k37": 37, "k38": 38, "k39": 39,
+        "k40": 40, "k41": 41, "k42": 42, "k43": 43, "k44": 44,
+        "k45": 45, "k46": 46, "k47": 47, "k48": 48, "k49": 49,
+        "k50": 50, "k51": 51, "k52": 52, "k53": 53, "k54": 54,
+        "k55": 55, "k56": 56, "k57": 57, "k58": 58, "k59": 59,
+        "k60": 60, "k61": 61, "k62": 62, "k63": 63, "k64": 64,
+        "k65": 65, "k66": 66, "k67": 67, "k68": 68, "k69": 69,
+        "k70": 70, "k71": 71, "k72": 72, "k73": 73, "k74": 74,
+        "k75": 75, "k76": 76, "k77": 77, "k78": 78, "k79": 79,
+        "k80": 80, "k81": 81, "k82": 82, "k83": 83, "k84": 84,
+        "k85": 85, "k86": 86, "k87": 87, "k88": 88, "k89": 89,
+        "k90": 90, "k91": 91, "k92": 92, "k93": 93, "k94": 94,
+        "k95": 95, "k96": 96, "k97": 97, "k98": 98, "k99": 99
+    });
+    let expected: Map = (0..100)
+        .map(|i| (format!("k{i}"), Value::from(i)))
+        .collect();
+    assert_eq!(object, Value::Object(expected));
+}